@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+
+use crate::{BufferType, Endianness, MagicMatch, OutputFormat};
+
+/// Everything learned about a file while classifying it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Classification {
+    pub buffer_type: BufferType,
+    /// The human readable description, such as `ISO 8859-1 text`.
+    pub description: String,
+    pub mime_type: String,
+    /// The charset name used in MIME output, as understood by `iconv` and
+    /// HTTP clients, or `binary` when the contents are not text.
+    pub charset: String,
+    /// The byte order of UTF-16 or UTF-32 text.
+    pub endianness: Option<Endianness>,
+    /// Whether the text starts with a byte order mark.
+    pub bom: bool,
+    /// Every text encoding the contents are still valid in, in the order
+    /// they are preferred. `buffer_type` is the first of these for text.
+    pub candidates: Vec<BufferType>,
+    /// Extensions usually given to this kind of file, without the dot.
+    pub extensions: Vec<String>,
+}
+
+impl Classification {
+    pub(crate) fn new(buffer_type: BufferType) -> Self {
+        Self::text(buffer_type, None, false, Vec::new())
+    }
+
+    pub(crate) fn text(
+        buffer_type: BufferType,
+        endianness: Option<Endianness>,
+        bom: bool,
+        candidates: Vec<BufferType>,
+    ) -> Self {
+        let mime_type = match buffer_type {
+            BufferType::Empty => "inode/x-empty",
+            BufferType::Data | BufferType::Magic => "application/octet-stream",
+            BufferType::Directory => "inode/directory",
+            BufferType::Symlink => "inode/symlink",
+            BufferType::Fifo => "inode/fifo",
+            BufferType::Socket => "inode/socket",
+            BufferType::BlockDevice => "inode/blockdevice",
+            BufferType::CharDevice => "inode/chardevice",
+            _ => "text/plain",
+        };
+        let description = match buffer_type {
+            BufferType::Utf16 if !bom => "UTF-16 text (no BOM)",
+            _ => buffer_type.description(),
+        };
+        Self {
+            buffer_type,
+            description: description.to_owned(),
+            mime_type: mime_type.to_owned(),
+            charset: charset(buffer_type, endianness).to_owned(),
+            endianness,
+            bom,
+            candidates,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// A file recognised by a [`Detector`](crate::Detector), which is
+    /// described as binary data of `mime_type`.
+    pub fn binary(buffer_type: BufferType, description: String, mime_type: &str) -> Self {
+        Self {
+            description,
+            mime_type: mime_type.to_owned(),
+            ..Self::new(buffer_type)
+        }
+    }
+
+    /// Combines a magic match with the encoding check of the same buffer.
+    /// Entries that only apply to text also describe the encoding. Like GNU
+    /// file, the charset always comes from the encoding check, as does the
+    /// MIME type of text matched by entries that give none.
+    pub(crate) fn magic(found: MagicMatch, text: Classification, text_test: bool) -> Self {
+        let mut classification = if text_test {
+            Self {
+                description: format!("{}, {}", found.description, text.description),
+                ..text
+            }
+        } else if text.buffer_type.is_text() {
+            Self {
+                description: found.description,
+                candidates: Vec::new(),
+                ..text
+            }
+        } else {
+            Self {
+                description: found.description,
+                ..Self::new(BufferType::Magic)
+            }
+        };
+        classification.buffer_type = BufferType::Magic;
+        if let Some(mime) = found.mime {
+            classification.mime_type = mime;
+        }
+        classification.extensions = found.extensions;
+        classification
+    }
+
+    /// The `type/subtype; charset=encoding` string printed by `file -i`.
+    pub fn mime(&self) -> String {
+        format!("{}; charset={}", self.mime_type, self.charset)
+    }
+
+    /// The message printed after the path. The JSON formats carry every
+    /// field separately, so they fall back to the description.
+    pub fn format(&self, format: OutputFormat) -> Cow<'_, str> {
+        match format {
+            OutputFormat::Description | OutputFormat::Json | OutputFormat::Ndjson => {
+                Cow::Borrowed(&self.description)
+            }
+            OutputFormat::Mime => Cow::Owned(self.mime()),
+            OutputFormat::MimeType => Cow::Borrowed(&self.mime_type),
+            OutputFormat::MimeEncoding => Cow::Borrowed(&self.charset),
+        }
+    }
+}
+
+const fn charset(buffer_type: BufferType, endianness: Option<Endianness>) -> &'static str {
+    match (buffer_type, endianness) {
+        (BufferType::Ascii, _) => "us-ascii",
+        (BufferType::Latin1, _) => "iso-8859-1",
+        (BufferType::Utf8, _) => "utf-8",
+        (BufferType::Utf16, Some(Endianness::BigEndian)) => "utf-16be",
+        (BufferType::Utf16, _) => "utf-16le",
+        (BufferType::Utf32, Some(Endianness::BigEndian)) => "utf-32be",
+        (BufferType::Utf32, _) => "utf-32le",
+        (BufferType::Gb, _) => "gb18030",
+        (BufferType::Text { charset, .. }, _) => charset,
+        _ => "binary",
+    }
+}
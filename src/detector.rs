@@ -0,0 +1,146 @@
+use std::sync::OnceLock;
+
+use crate::{formats, Classification};
+
+/// Recognises one binary format, or a family of them, from the first bytes
+/// of a file and, when they could be read, its last bytes.
+///
+/// Detectors run before magic rules and the text encoding checks. Register
+/// one in [`Detectors`] and pass that as [`Options::detectors`] to have
+/// [`file_with_options`] and [`classify_file_with_options`] name a format
+/// of your own.
+///
+/// [`Options::detectors`]: crate::Options::detectors
+/// [`file_with_options`]: crate::file_with_options
+/// [`classify_file_with_options`]: crate::classify_file_with_options
+pub trait Detector: Send + Sync {
+    /// A cheap test of the signature, such as the magic number at the start
+    /// of `head`. [`Detector::detect`] is only called when it passes.
+    fn probe(&self, head: &[u8], tail: Option<&[u8]>) -> bool;
+
+    /// Parses as much of the format as it needs to describe it. `head` holds
+    /// up to the first 1 MiB of the file, and `tail` the last few KiB when
+    /// the file was read through a seekable reader or fits in `head`.
+    fn detect(&self, head: &[u8], tail: Option<&[u8]>) -> Option<Classification>;
+
+    /// Detectors with a higher priority run first, and equal ones in the
+    /// order they were registered. Built-in detectors have priority 0, so a
+    /// detector for a format built on one of theirs, such as a zip-based
+    /// package, needs a positive priority to be tried before them.
+    fn priority(&self) -> i32 {
+        0
+    }
+}
+
+/// The detectors tried on every file, in priority order.
+pub struct Detectors {
+    detectors: Vec<Box<dyn Detector>>,
+}
+
+impl Detectors {
+    /// The built-in detectors for executables, images, compressed streams,
+    /// archives, documents and audio.
+    pub fn new() -> Self {
+        let mut detectors = Self::empty();
+        for detector in formats::detectors() {
+            detectors.insert(detector);
+        }
+        detectors
+    }
+
+    /// A registry without any detectors, not even the built-in ones.
+    pub fn empty() -> Self {
+        Self {
+            detectors: Vec::new(),
+        }
+    }
+
+    /// Adds `detector` after every registered detector of the same or a
+    /// higher priority.
+    pub fn register(&mut self, detector: impl Detector + 'static) {
+        self.insert(Box::new(detector));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.detectors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.detectors.len()
+    }
+
+    /// Runs the detectors in priority order, returning the first
+    /// classification made by one whose probe passes.
+    pub fn identify(&self, head: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+        self.detectors
+            .iter()
+            .filter(|detector| detector.probe(head, tail))
+            .find_map(|detector| detector.detect(head, tail))
+    }
+
+    /// The built-in detectors, shared by the entry points that take no
+    /// [`Options`](crate::Options).
+    pub(crate) fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<Detectors> = OnceLock::new();
+        BUILTIN.get_or_init(Self::new)
+    }
+
+    fn insert(&mut self, detector: Box<dyn Detector>) {
+        let priority = detector.priority();
+        let at = self
+            .detectors
+            .partition_point(|registered| registered.priority() >= priority);
+        self.detectors.insert(at, detector);
+    }
+}
+
+impl Default for Detectors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BufferType;
+
+    struct Signature(&'static [u8], &'static str, i32);
+
+    impl Detector for Signature {
+        fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+            head.starts_with(self.0)
+        }
+
+        fn detect(&self, _: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+            Some(Classification::binary(
+                BufferType::Custom,
+                self.1.to_owned(),
+                "application/x-example",
+            ))
+        }
+
+        fn priority(&self) -> i32 {
+            self.2
+        }
+    }
+
+    #[test]
+    fn test_priority() {
+        let zip = b"PK\x03\x04\x14\0\0\0\x08\0";
+        let mut detectors = Detectors::new();
+        let builtin = detectors.len();
+        detectors.register(Signature(b"PK", "late", 0));
+        assert_eq!(
+            detectors.identify(zip, None).unwrap().buffer_type,
+            BufferType::Archive
+        );
+        detectors.register(Signature(b"PK", "first", 1));
+        detectors.register(Signature(b"PK", "second", 1));
+        assert_eq!(detectors.len(), builtin + 3);
+        let classification = detectors.identify(zip, None).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Custom);
+        assert_eq!(classification.description, "first");
+        assert!(Detectors::empty().identify(zip, None).is_none());
+    }
+}
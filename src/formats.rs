@@ -0,0 +1,132 @@
+pub(crate) mod archive;
+pub(crate) mod audio;
+pub(crate) mod compressed;
+pub(crate) mod document;
+pub(crate) mod elf;
+pub(crate) mod image;
+pub(crate) mod macho;
+pub(crate) mod package;
+pub(crate) mod pe;
+
+use std::{
+    borrow::Cow,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::Detector;
+
+/// The native parsers for binary formats, in the order they are tried. Zip
+/// packages come before plain zip archives, and disk images, which are only
+/// known by their last bytes, come last.
+pub(crate) fn detectors() -> Vec<Box<dyn Detector>> {
+    vec![
+        Box::new(elf::ElfDetector),
+        Box::new(pe::PeDetector),
+        Box::new(macho::MachODetector),
+        Box::new(image::ImageDetector),
+        Box::new(compressed::CompressedDetector),
+        Box::new(package::PackageDetector),
+        Box::new(archive::ArchiveDetector),
+        Box::new(document::DocumentDetector),
+        Box::new(audio::AudioDetector),
+        Box::new(archive::DiskImageDetector),
+    ]
+}
+
+/// Whether `data` starts with any of `signatures`.
+pub(crate) fn starts_with_any(data: &[u8], signatures: &[&[u8]]) -> bool {
+    signatures
+        .iter()
+        .any(|signature| data.starts_with(signature))
+}
+
+/// Bounds-checked fixed-width reads from a buffer in one byte order.
+#[derive(Copy, Clone)]
+pub(crate) struct Bytes<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Bytes<'a> {
+    pub(crate) const fn new(data: &'a [u8], big_endian: bool) -> Self {
+        Self { data, big_endian }
+    }
+
+    pub(crate) fn slice(&self, at: u64, length: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(at).ok()?;
+        let end = start.checked_add(usize::try_from(length).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    fn array<const N: usize>(&self, at: u64) -> Option<[u8; N]> {
+        self.slice(at, N as u64)?.try_into().ok()
+    }
+
+    pub(crate) fn u16(&self, at: u64) -> Option<u16> {
+        let bytes = self.array(at)?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn u32(&self, at: u64) -> Option<u32> {
+        let bytes = self.array(at)?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn u64(&self, at: u64) -> Option<u64> {
+        let bytes = self.array(at)?;
+        Some(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+}
+
+/// Random access to a file, for the parsers whose tables may lie anywhere in
+/// it rather than in the first bytes read.
+pub(crate) trait Source {
+    /// The `length` bytes at offset `at`, or `None` when the file ends first.
+    fn read(&mut self, at: u64, length: u64) -> Option<Cow<'_, [u8]>>;
+}
+
+impl Source for &[u8] {
+    fn read(&mut self, at: u64, length: u64) -> Option<Cow<'_, [u8]>> {
+        Bytes::new(self, false).slice(at, length).map(Cow::Borrowed)
+    }
+}
+
+/// A seekable reader as a [`Source`], with offsets counted from where it was
+/// positioned when wrapped.
+pub(crate) struct Seekable<R> {
+    reader: R,
+    start: u64,
+}
+
+impl<R: Read + Seek> Seekable<R> {
+    pub(crate) fn new(mut reader: R) -> Option<Self> {
+        let start = reader.stream_position().ok()?;
+        Some(Self { reader, start })
+    }
+}
+
+impl<R: Read + Seek> Source for Seekable<R> {
+    fn read(&mut self, at: u64, length: u64) -> Option<Cow<'_, [u8]>> {
+        let at = self.start.checked_add(at)?;
+        self.reader.seek(SeekFrom::Start(at)).ok()?;
+        let mut data = Vec::new();
+        self.reader
+            .by_ref()
+            .take(length)
+            .read_to_end(&mut data)
+            .ok()?;
+        (data.len() as u64 == length).then_some(Cow::Owned(data))
+    }
+}
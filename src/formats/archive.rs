@@ -0,0 +1,297 @@
+use crate::{
+    formats::{starts_with_any, Bytes},
+    BufferType, Classification, Detector,
+};
+
+pub(crate) const TAR_BLOCK: usize = 512;
+const UDIF_TRAILER: usize = 512;
+const TAR_CHECKSUM: std::ops::Range<usize> = 148..156;
+/// The leading bytes of every format but tar, whose magic is not at the
+/// start of the header and is missing from V7 archives.
+const SIGNATURES: &[&[u8]] = &[
+    b"PK",
+    b"7z\xbc\xaf\x27\x1c",
+    b"Rar!\x1a\x07",
+    b"0707",
+    b"\xc7\x71",
+    b"\x71\xc7",
+    b"!<arch>\n",
+    b"!<thin>\n",
+];
+
+/// Recognises archives that bundle several files.
+pub(crate) fn identify(data: &[u8]) -> Option<Classification> {
+    let (description, mime_type, extensions): (_, _, &[_]) = if let Some(zip) = zip(data) {
+        (zip, "application/zip", &["zip"])
+    } else if let Some([major, minor]) = data
+        .strip_prefix(b"7z\xbc\xaf\x27\x1c")
+        .and_then(|rest| rest.get(..2))
+    {
+        (
+            format!("7-zip archive data, version {major}.{minor}"),
+            "application/x-7z-compressed",
+            &["7z"],
+        )
+    } else if data.starts_with(b"Rar!\x1a\x07\x00") {
+        (
+            String::from("RAR archive data, v4"),
+            "application/x-rar",
+            &["rar"],
+        )
+    } else if data.starts_with(b"Rar!\x1a\x07\x01\x00") {
+        (
+            String::from("RAR archive data, v5"),
+            "application/x-rar",
+            &["rar"],
+        )
+    } else if let Some(tar) = tar(data) {
+        (tar, "application/x-tar", &["tar"])
+    } else if let Some(cpio) = cpio(data) {
+        (cpio, "application/x-cpio", &["cpio"])
+    } else if let Some(rest) = data.strip_prefix(b"!<arch>\n") {
+        match debian(rest) {
+            Some(version) => (
+                format!("Debian binary package (format {version})"),
+                "application/vnd.debian.binary-package",
+                &["deb"],
+            ),
+            None => (
+                String::from("current ar archive"),
+                "application/x-archive",
+                &["a"],
+            ),
+        }
+    } else if data.starts_with(b"!<thin>\n") {
+        (
+            String::from("thin archive"),
+            "application/x-archive",
+            &["a"],
+        )
+    } else {
+        return None;
+    };
+    let mut classification = Classification::binary(BufferType::Archive, description, mime_type);
+    classification.extensions = extensions.iter().map(|&e| e.to_owned()).collect();
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with an archive signature, or long
+/// enough to hold a tar header.
+pub(crate) struct ArchiveDetector;
+
+impl Detector for ArchiveDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        starts_with_any(head, SIGNATURES) || head.len() >= TAR_BLOCK
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+/// Recognises an Apple UDIF disk image from the `koly` trailer in its last
+/// 512 bytes.
+pub(crate) fn disk_image(tail: &[u8]) -> Option<Classification> {
+    let trailer = tail.get(tail.len().checked_sub(UDIF_TRAILER)?..)?;
+    if !trailer.starts_with(b"koly") || Bytes::new(trailer, true).u32(4)? != 4 {
+        return None;
+    }
+    let mut classification = Classification::binary(
+        BufferType::Archive,
+        String::from("Apple disk image (UDIF)"),
+        "application/x-apple-diskimage",
+    );
+    classification.extensions = vec![String::from("dmg")];
+    Some(classification)
+}
+
+/// Runs [`disk_image`] on the end of files whose tail is known.
+pub(crate) struct DiskImageDetector;
+
+impl Detector for DiskImageDetector {
+    fn probe(&self, _: &[u8], tail: Option<&[u8]>) -> bool {
+        tail.is_some_and(|tail| tail.len() >= UDIF_TRAILER)
+    }
+
+    fn detect(&self, _: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+        disk_image(tail?)
+    }
+}
+
+/// Whether `data` starts a zip archive, including an empty one.
+pub(crate) fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06")
+}
+
+/// Whether `data` starts with a tar header of any variant.
+pub(crate) fn is_tar(data: &[u8]) -> bool {
+    tar(data).is_some()
+}
+
+/// Describes the first local file header of a zip archive.
+fn zip(data: &[u8]) -> Option<String> {
+    if data.starts_with(b"PK\x05\x06") {
+        return Some(String::from("Zip archive data (empty)"));
+    }
+    if data.starts_with(b"PK\x07\x08") {
+        return Some(String::from("Zip multi-volume archive data"));
+    }
+    if !data.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+    let bytes = Bytes::new(data, false);
+    let version = bytes.u16(4)?;
+    let method = match bytes.u16(8)? {
+        0 => String::from("store"),
+        8 => String::from("deflate"),
+        9 => String::from("deflate64"),
+        12 => String::from("bzip2"),
+        14 => String::from("lzma"),
+        93 => String::from("zstd"),
+        95 => String::from("xz"),
+        98 => String::from("ppmd"),
+        method => method.to_string(),
+    };
+    Some(format!(
+        "Zip archive data, at least v{}.{} to extract, compression method={method}",
+        version / 10,
+        version % 10
+    ))
+}
+
+/// Tells V7, POSIX, GNU and pax tar headers apart. Every variant shares the
+/// header checksum, which is verified so arbitrary data is not taken for a
+/// V7 archive.
+fn tar(data: &[u8]) -> Option<String> {
+    let header = data.get(..TAR_BLOCK)?;
+    if header[0] == 0 || !tar_checksum(header) {
+        return None;
+    }
+    let kind = match &header[257..265] {
+        b"ustar  \0" => "POSIX tar archive (GNU)",
+        [b'u', b's', b't', b'a', b'r', 0, ..] if matches!(header[156], b'x' | b'g') => {
+            "POSIX tar archive (pax)"
+        }
+        [b'u', b's', b't', b'a', b'r', 0, ..] => "POSIX tar archive",
+        _ => "tar archive (V7)",
+    };
+    Some(kind.to_owned())
+}
+
+fn tar_checksum(header: &[u8]) -> bool {
+    let field = &header[TAR_CHECKSUM];
+    let digits = field
+        .iter()
+        .skip_while(|&&byte| byte == b' ')
+        .take_while(|byte| (b'0'..=b'7').contains(byte));
+    let mut expected = 0u32;
+    let mut found = false;
+    for digit in digits {
+        expected = expected * 8 + (digit - b'0') as u32;
+        found = true;
+    }
+    let sum: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(index, &byte)| {
+            if TAR_CHECKSUM.contains(&index) {
+                b' ' as u32
+            } else {
+                byte as u32
+            }
+        })
+        .sum();
+    found && sum == expected
+}
+
+fn cpio(data: &[u8]) -> Option<String> {
+    let kind = match data.get(..6)? {
+        b"070707" => "ASCII cpio archive (pre-SVR4 or odc)",
+        b"070701" => "ASCII cpio archive (SVR4 with no CRC)",
+        b"070702" => "ASCII cpio archive (SVR4 with CRC)",
+        [0xC7, 0x71, ..] => "cpio archive",
+        [0x71, 0xC7, ..] => "byte-swapped cpio archive",
+        _ => return None,
+    };
+    Some(kind.to_owned())
+}
+
+/// The format version of a Debian package, whose first ar member is
+/// `debian-binary`.
+fn debian(members: &[u8]) -> Option<String> {
+    if !members.starts_with(b"debian-binary ") && !members.starts_with(b"debian-binary/") {
+        return None;
+    }
+    let contents = members.get(60..)?;
+    let end = contents.iter().position(|&byte| byte == b'\n')?;
+    Some(String::from_utf8_lossy(&contents[..end]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(data: &[u8]) -> String {
+        identify(data).unwrap().description
+    }
+
+    fn tar_header(magic: &[u8], kind: u8) -> Vec<u8> {
+        let mut header = vec![0; TAR_BLOCK];
+        header[..8].copy_from_slice(b"file.txt");
+        header[156] = kind;
+        header[257..257 + magic.len()].copy_from_slice(magic);
+        header[TAR_CHECKSUM].fill(b' ');
+        let sum: u32 = header.iter().map(|&byte| byte as u32).sum();
+        header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+        header
+    }
+
+    #[test]
+    fn test_identify() {
+        let zip = b"PK\x03\x04\x14\0\0\0\x08\0";
+        let classification = identify(zip).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Archive);
+        assert_eq!(
+            classification.description,
+            "Zip archive data, at least v2.0 to extract, compression method=deflate"
+        );
+        assert_eq!(describe(b"PK\x05\x06"), "Zip archive data (empty)");
+        assert_eq!(
+            describe(b"7z\xbc\xaf\x27\x1c\0\x04"),
+            "7-zip archive data, version 0.4"
+        );
+        assert_eq!(describe(b"Rar!\x1a\x07\x01\0"), "RAR archive data, v5");
+        assert_eq!(
+            describe(b"070701000000"),
+            "ASCII cpio archive (SVR4 with no CRC)"
+        );
+        assert_eq!(describe(b"!<arch>\n/               "), "current ar archive");
+        let deb = b"!<arch>\ndebian-binary   1700000000  0     0     100644  4         `\n2.0\n";
+        let classification = identify(deb).unwrap();
+        assert_eq!(
+            classification.description,
+            "Debian binary package (format 2.0)"
+        );
+        assert_eq!(classification.extensions, ["deb"]);
+    }
+
+    #[test]
+    fn test_tar() {
+        assert_eq!(
+            describe(&tar_header(b"ustar\x0000", b'0')),
+            "POSIX tar archive"
+        );
+        assert_eq!(
+            describe(&tar_header(b"ustar  \0", b'0')),
+            "POSIX tar archive (GNU)"
+        );
+        assert_eq!(
+            describe(&tar_header(b"ustar\x0000", b'x')),
+            "POSIX tar archive (pax)"
+        );
+        assert_eq!(describe(&tar_header(b"", b'0')), "tar archive (V7)");
+        let mut corrupt = tar_header(b"", b'0');
+        corrupt[0] = b'g';
+        assert!(identify(&corrupt).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Write;
+
+use crate::{BufferType, Classification, Detector};
+
+/// The ID3v1 tag some MP3 encoders append, in the last 128 bytes of a file.
+const ID3V1_SIZE: usize = 128;
+const ID3V2_FOOTER: u8 = 0x10;
+
+/// Kilobits per second by bitrate index, for MPEG-1 layers I, II and III
+/// and then MPEG-2 layer I and layers II and III.
+const BITRATES: [[u16; 15]; 5] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// An MPEG audio frame header.
+struct Frame {
+    /// 1 for MPEG-1, 2 for MPEG-2 and 25 for MPEG-2.5.
+    version: u8,
+    layer: u8,
+    bitrate: u16,
+    sample_rate: u32,
+    padding: bool,
+    channels: &'static str,
+}
+
+/// Recognises MP3 and the other MPEG audio layers, behind an ID3v2 tag or
+/// bare. A bare frame header is only trusted when the next frame follows it
+/// or `tail` ends with an ID3v1 tag, since the sync pattern is short.
+pub(crate) fn identify(head: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+    let id3v1 = tail.is_some_and(|tail| {
+        tail.len() >= ID3V1_SIZE && tail[tail.len() - ID3V1_SIZE..].starts_with(b"TAG")
+    });
+    let mut description = if let Some(rest) = head.strip_prefix(b"ID3") {
+        let &[major, minor, flags, ref size @ ..] = rest.get(..7)? else {
+            return None;
+        };
+        if major == 0xFF || minor == 0xFF || size.iter().any(|&byte| byte & 0x80 != 0) {
+            return None;
+        }
+        let size = size
+            .iter()
+            .fold(0usize, |size, &byte| size << 7 | byte as usize);
+        let footer = if flags & ID3V2_FOOTER != 0 { 10 } else { 0 };
+        let mut description = format!("Audio file with ID3 version 2.{major}.{minor}");
+        if let Some(frame) = head.get(10 + size + footer..).and_then(frame) {
+            let _ = write!(description, ", contains: {frame}");
+        }
+        description
+    } else {
+        let first = frame(head)?;
+        let next = head.get(first.length()..).and_then(frame);
+        if next.is_none() && !id3v1 {
+            return None;
+        }
+        first.to_string()
+    };
+    if id3v1 {
+        description.push_str(", with ID3 version 1 tag");
+    }
+    let mut classification = Classification::binary(BufferType::Audio, description, "audio/mpeg");
+    classification.extensions = vec![String::from("mp3")];
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with an ID3v2 tag or a frame sync.
+pub(crate) struct AudioDetector;
+
+impl Detector for AudioDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        head.starts_with(b"ID3") || head.first() == Some(&0xFF)
+    }
+
+    fn detect(&self, head: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+        identify(head, tail)
+    }
+}
+
+/// Parses the frame header at the start of `data`.
+fn frame(data: &[u8]) -> Option<Frame> {
+    let &[0xFF, second, third, fourth, ..] = data else {
+        return None;
+    };
+    if second & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = match (second >> 3) & 0x03 {
+        0 => 25,
+        2 => 2,
+        3 => 1,
+        _ => return None,
+    };
+    let layer = match (second >> 1) & 0x03 {
+        1 => 3,
+        2 => 2,
+        3 => 1,
+        _ => return None,
+    };
+    let table = match (version, layer) {
+        (1, layer) => layer as usize - 1,
+        (_, 1) => 3,
+        _ => 4,
+    };
+    let bitrate = *BITRATES[table].get((third >> 4) as usize)?;
+    let sample_rate = [44_100, 48_000, 32_000].get(((third >> 2) & 0x03) as usize)?
+        / match version {
+            1 => 1,
+            2 => 2,
+            _ => 4,
+        };
+    if bitrate == 0 {
+        return None;
+    }
+    let channels = match fourth >> 6 {
+        0 => "Stereo",
+        1 => "JntStereo",
+        2 => "2x Monaural",
+        _ => "Monaural",
+    };
+    Some(Frame {
+        version,
+        layer,
+        bitrate,
+        sample_rate,
+        padding: third & 0x02 != 0,
+        channels,
+    })
+}
+
+impl Frame {
+    /// The length of the frame in bytes, header included.
+    fn length(&self) -> usize {
+        let bits = self.bitrate as usize * 1000;
+        let sample_rate = self.sample_rate as usize;
+        let padding = self.padding as usize;
+        match (self.layer, self.version) {
+            (1, _) => (12 * bits / sample_rate + padding) * 4,
+            (3, 2 | 25) => 72 * bits / sample_rate + padding,
+            _ => 144 * bits / sample_rate + padding,
+        }
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let layer = ["I", "II", "III"][self.layer as usize - 1];
+        let version = match self.version {
+            25 => "2.5",
+            2 => "2",
+            _ => "1",
+        };
+        write!(
+            f,
+            "MPEG ADTS, layer {layer}, v{version}, {} kbps, {} kHz, {}",
+            self.bitrate,
+            self.sample_rate as f64 / 1000.0,
+            self.channels
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A silent MPEG-1 layer III frame at 128 kbps and 44.1 kHz.
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0; 417];
+        frame[..4].copy_from_slice(b"\xff\xfb\x90\x44");
+        frame
+    }
+
+    #[test]
+    fn test_identify() {
+        let mp3 = [frame(), frame()].concat();
+        let classification = identify(&mp3, None).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Audio);
+        assert_eq!(
+            classification.description,
+            "MPEG ADTS, layer III, v1, 128 kbps, 44.1 kHz, JntStereo"
+        );
+        // a single frame could be chance, unless an ID3v1 tag ends the file
+        assert!(identify(&frame(), None).is_none());
+        let mut tagged = frame();
+        tagged.extend_from_slice(b"TAG");
+        tagged.resize(tagged.len() + ID3V1_SIZE - 3, 0);
+        assert_eq!(
+            identify(&tagged, Some(&tagged)).unwrap().description,
+            "MPEG ADTS, layer III, v1, 128 kbps, 44.1 kHz, JntStereo, with ID3 version 1 tag"
+        );
+
+        let mut id3 = b"ID3\x04\0\0\0\0\0\x02\0\0".to_vec();
+        id3.extend(frame());
+        assert_eq!(
+            identify(&id3, None).unwrap().description,
+            "Audio file with ID3 version 2.4.0, contains: MPEG ADTS, layer III, v1, 128 kbps, \
+             44.1 kHz, JntStereo"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    fmt::Write,
+    io::{self, Read},
+};
+
+use crate::{
+    formats::{starts_with_any, Bytes},
+    magic::evaluate::format_date,
+    BufferType, Classification, Detector,
+};
+
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const ZSTD_MAGIC: u32 = 0xFD2F_B528;
+/// Skippable frames use any magic from `0x184D2A50` to `0x184D2A5F`.
+const ZSTD_SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+const SIGNATURES: &[&[u8]] = &[
+    b"\x1f\x8b",
+    b"BZh",
+    b"\xfd7zXZ\0",
+    b"\x28\xb5\x2f\xfd",
+    b"\x04\x22\x4d\x18",
+    b"\x02\x21\x4c\x18",
+    b"\x1f\x9d",
+];
+
+/// Recognises single-stream compression formats.
+pub(crate) fn identify(data: &[u8]) -> Option<Classification> {
+    let (description, mime_type, extensions): (_, _, &[_]) = if let Some(gzip) = gzip(data) {
+        (gzip, "application/gzip", &["gz", "tgz"])
+    } else if let Some(level) = data.strip_prefix(b"BZh").and_then(|rest| rest.first()) {
+        if !(b'1'..=b'9').contains(level) {
+            return None;
+        }
+        let size = (level - b'0') as u32 * 100;
+        (
+            format!("bzip2 compressed data, block size = {size}k"),
+            "application/x-bzip2",
+            &["bz2"],
+        )
+    } else if data.starts_with(b"\xfd7zXZ\0") {
+        let check = match data.get(7)? & 0x0F {
+            0 => "NONE",
+            1 => "CRC32",
+            4 => "CRC64",
+            10 => "SHA-256",
+            _ => "unknown",
+        };
+        (
+            format!("XZ compressed data, checksum {check}"),
+            "application/x-xz",
+            &["xz"],
+        )
+    } else if let Some(zstd) = zstd(data) {
+        (zstd, "application/zstd", &["zst"])
+    } else if data.starts_with(b"\x04\x22\x4d\x18") {
+        (
+            String::from("LZ4 compressed data (v1.4+)"),
+            "application/x-lz4",
+            &["lz4"],
+        )
+    } else if data.starts_with(b"\x02\x21\x4c\x18") {
+        (
+            String::from("LZ4 compressed data (v0.1-v0.9)"),
+            "application/x-lz4",
+            &["lz4"],
+        )
+    } else if data.starts_with(b"\x1f\x9d") {
+        (
+            format!("compress'd data {} bits", data.get(2)? & 0x1F),
+            "application/x-compress",
+            &["Z"],
+        )
+    } else {
+        return None;
+    };
+    let mut classification = Classification::binary(BufferType::Compressed, description, mime_type);
+    classification.extensions = extensions.iter().map(|&e| e.to_owned()).collect();
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with a compressed stream signature.
+pub(crate) struct CompressedDetector;
+
+impl Detector for CompressedDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        starts_with_any(head, SIGNATURES)
+            || Bytes::new(head, false)
+                .u32(0)
+                .is_some_and(|magic| magic & !0x0F == ZSTD_SKIPPABLE_MAGIC)
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+/// Wraps `reader`, which starts with `head`, in a decoder for the gzip,
+/// bzip2, xz or Zstandard stream `head` begins. Other formats, and Zstandard
+/// frames whose header does not parse, return `None`.
+pub(crate) fn decoder<'a>(head: &[u8], mut reader: impl Read + 'a) -> Option<Box<dyn Read + 'a>> {
+    if gzip(head).is_some() {
+        Some(Box::new(flate2::read::MultiGzDecoder::new(reader)))
+    } else if head.starts_with(b"BZh") {
+        Some(Box::new(bzip2::read::MultiBzDecoder::new(reader)))
+    } else if head.starts_with(b"\xfd7zXZ\0") {
+        Some(Box::new(lzma_rust2::XzReader::new(reader, true)))
+    } else {
+        let skipped = zstd_frames(head).1?;
+        io::copy(&mut reader.by_ref().take(skipped), &mut io::sink()).ok()?;
+        let decoder = ruzstd::decoding::StreamingDecoder::new(reader).ok()?;
+        Some(Box::new(decoder))
+    }
+}
+
+/// Describes a gzip member header, including the original file name and
+/// modification time when they were recorded.
+fn gzip(data: &[u8]) -> Option<String> {
+    if !data.starts_with(b"\x1f\x8b\x08") {
+        return None;
+    }
+    let bytes = Bytes::new(data, false);
+    let flags = *data.get(3)?;
+    let mtime = bytes.u32(4)?;
+    let mut description = String::from("gzip compressed data");
+    if flags & GZIP_FNAME != 0 {
+        let mut at = 10;
+        if flags & GZIP_FEXTRA != 0 {
+            at += 2 + bytes.u16(10)? as usize;
+        }
+        let name = data.get(at..)?;
+        let end = name
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(name.len());
+        let _ = write!(
+            description,
+            ", was \"{}\"",
+            String::from_utf8_lossy(&name[..end])
+        );
+    }
+    if mtime != 0 {
+        let _ = write!(
+            description,
+            ", last modified: {}",
+            format_date(i64::from(mtime))
+        );
+    }
+    match data.get(8)? {
+        2 => description.push_str(", max compression"),
+        4 => description.push_str(", max speed"),
+        _ => {}
+    }
+    let os = match data.get(9)? {
+        0 => "FAT filesystem (MS-DOS, OS/2, NT)",
+        3 => "Unix",
+        7 => "Macintosh",
+        10 => "TOPS/20",
+        11 => "NTFS filesystem (NT)",
+        _ => "",
+    };
+    if !os.is_empty() {
+        let _ = write!(description, ", from {os}");
+    }
+    Some(description)
+}
+
+/// Counts the skippable frames at the start of `data`, and finds where the
+/// first Zstandard frame after them begins.
+fn zstd_frames(data: &[u8]) -> (usize, Option<u64>) {
+    let bytes = Bytes::new(data, false);
+    let mut skippable = 0;
+    let mut at = 0;
+    loop {
+        match bytes.u32(at) {
+            Some(ZSTD_MAGIC) => return (skippable, Some(at)),
+            Some(magic) if magic & !0x0F == ZSTD_SKIPPABLE_MAGIC => {
+                skippable += 1;
+                match bytes.u32(at + 4) {
+                    Some(length) => at += 8 + length as u64,
+                    None => return (skippable, None),
+                }
+            }
+            _ => return (skippable, None),
+        }
+    }
+}
+
+/// Describes the first Zstandard frame after any skippable frames.
+fn zstd(data: &[u8]) -> Option<String> {
+    let bytes = Bytes::new(data, false);
+    let (skippable, frame) = zstd_frames(data);
+    let Some(at) = frame else {
+        return (skippable > 0).then(|| String::from("Zstandard skippable frame"));
+    };
+    let mut description = String::from("Zstandard compressed data (v0.8+)");
+    if skippable > 0 {
+        let _ = write!(
+            description,
+            ", after {skippable} skippable frame{}",
+            if skippable == 1 { "" } else { "s" }
+        );
+    }
+    let Some(&descriptor) = data.get(at as usize + 4) else {
+        return Some(description);
+    };
+    let single_segment = descriptor & 0x20 != 0;
+    let dictionary_size = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let dictionary_at = at + 5 + u64::from(!single_segment);
+    let dictionary = match dictionary_size {
+        0 => Some(0),
+        1 => data.get(dictionary_at as usize).map(|&id| id as u32),
+        2 => bytes.u16(dictionary_at).map(u32::from),
+        _ => bytes.u32(dictionary_at),
+    };
+    match dictionary {
+        Some(0) => description.push_str(", Dictionary ID: None"),
+        Some(id) => {
+            let _ = write!(description, ", Dictionary ID: {id}");
+        }
+        None => {}
+    }
+    let size_at = dictionary_at + dictionary_size;
+    let content_size = match descriptor >> 6 {
+        0 if single_segment => data.get(size_at as usize).map(|&size| size as u64),
+        0 => None,
+        1 => bytes.u16(size_at).map(|size| size as u64 + 256),
+        2 => bytes.u32(size_at).map(u64::from),
+        _ => bytes.u64(size_at),
+    };
+    if let Some(size) = content_size {
+        let _ = write!(description, ", original size {size}");
+    }
+    Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(data: &[u8]) -> String {
+        identify(data).unwrap().description
+    }
+
+    #[test]
+    fn test_identify() {
+        let gzip = b"\x1f\x8b\x08\x08\x80\x00\x92\x65\x02\x03foo.txt\0";
+        let classification = identify(gzip).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Compressed);
+        assert_eq!(classification.mime_type, "application/gzip");
+        assert_eq!(
+            classification.description,
+            "gzip compressed data, was \"foo.txt\", last modified: Mon Jan  1 00:00:00 2024, \
+             max compression, from Unix"
+        );
+        assert_eq!(
+            describe(b"BZh91AY&SY"),
+            "bzip2 compressed data, block size = 900k"
+        );
+        assert!(identify(b"BZh0").is_none());
+        assert_eq!(
+            describe(b"\xfd7zXZ\0\0\x04"),
+            "XZ compressed data, checksum CRC64"
+        );
+        assert_eq!(describe(b"\x1f\x9d\x90"), "compress'd data 16 bits");
+        assert_eq!(
+            describe(b"\x04\x22\x4d\x18\x64"),
+            "LZ4 compressed data (v1.4+)"
+        );
+    }
+
+    #[test]
+    fn test_zstd() {
+        assert_eq!(
+            describe(b"\x28\xb5\x2f\xfd\x24\x05"),
+            "Zstandard compressed data (v0.8+), Dictionary ID: None, original size 5"
+        );
+        let skipped = b"\x5a\x2a\x4d\x18\x02\0\0\0xx\x28\xb5\x2f\xfd\x01\x58\x07\0";
+        assert_eq!(
+            describe(skipped),
+            "Zstandard compressed data (v0.8+), after 1 skippable frame, Dictionary ID: 7"
+        );
+        assert_eq!(
+            describe(b"\x50\x2a\x4d\x18\x08\0\0\0"),
+            "Zstandard skippable frame"
+        );
+    }
+}
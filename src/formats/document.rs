@@ -0,0 +1,68 @@
+use crate::{BufferType, Classification, Detector};
+
+/// How far from the end of a PDF its `%%EOF` marker may be. Writers leave a
+/// line ending or two after it, and some leave more.
+const PDF_EOF_WINDOW: usize = 1024;
+
+/// Recognises PDF documents from their header. When the end of the file is
+/// known, one without an `%%EOF` marker there is reported as truncated, as
+/// an interrupted download would be.
+pub(crate) fn identify(head: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+    let rest = head.strip_prefix(b"%PDF-")?;
+    let end = rest
+        .iter()
+        .position(|byte| !matches!(byte, b'0'..=b'9' | b'.'))
+        .unwrap_or(rest.len());
+    let version = std::str::from_utf8(&rest[..end]).ok()?;
+    if !version.contains('.') {
+        return None;
+    }
+    let mut description = format!("PDF document, version {version}");
+    if let Some(tail) = tail {
+        let trailer = &tail[tail.len().saturating_sub(PDF_EOF_WINDOW)..];
+        if !trailer.windows(5).any(|window| window == b"%%EOF") {
+            description.push_str(", truncated");
+        }
+    }
+    let mut classification =
+        Classification::binary(BufferType::Document, description, "application/pdf");
+    classification.extensions = vec![String::from("pdf")];
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with a PDF header.
+pub(crate) struct DocumentDetector;
+
+impl Detector for DocumentDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        head.starts_with(b"%PDF-")
+    }
+
+    fn detect(&self, head: &[u8], tail: Option<&[u8]>) -> Option<Classification> {
+        identify(head, tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify() {
+        let pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\ntrailer\n<</Root 1 0 R>>\n%%EOF\n";
+        let classification = identify(pdf, None).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Document);
+        assert_eq!(classification.description, "PDF document, version 1.7");
+        assert_eq!(classification.mime_type, "application/pdf");
+        assert_eq!(
+            identify(pdf, Some(pdf)).unwrap().description,
+            "PDF document, version 1.7"
+        );
+        let cut = &pdf[..20];
+        assert_eq!(
+            identify(cut, Some(cut)).unwrap().description,
+            "PDF document, version 1.7, truncated"
+        );
+        assert!(identify(b"%PDF-x", None).is_none());
+    }
+}
@@ -0,0 +1,532 @@
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    io::{Read, Seek},
+};
+
+use crate::{
+    formats::{Bytes, Seekable, Source},
+    BufferType, Classification, Detector,
+};
+
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const PT_NOTE: u32 = 4;
+const SHT_SYMTAB: u32 = 2;
+const DT_NULL: u64 = 0;
+const DT_FLAGS_1: u64 = 0x6FFF_FFFB;
+const DF_1_PIE: u64 = 0x0800_0000;
+const NT_GNU_ABI_TAG: u32 = 1;
+const NT_GNU_BUILD_ID: u32 = 3;
+/// The most of a segment read for its interpreter, dynamic entries or notes,
+/// which are all far smaller in real binaries.
+const SEGMENT_MAX: u64 = 64 * 1024;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Relocatable,
+    Executable,
+    Shared,
+    Core,
+    Other(u16),
+}
+
+/// The parts of an ELF file that show up in its description.
+struct Elf {
+    wide: bool,
+    big_endian: bool,
+    kind: Kind,
+    machine: u16,
+    version: u8,
+    os_abi: u8,
+    interpreter: Option<String>,
+    dynamic: bool,
+    pie: bool,
+    build_id: Option<Vec<u8>>,
+    abi_tag: Option<(u32, [u32; 3])>,
+    /// Whether there is a symbol table, when the section headers were seen.
+    symbols: Option<bool>,
+    sections: u16,
+}
+
+/// Parses an ELF header along with its program and section headers. Details
+/// stored beyond the end of `data` are left out of the description, and a
+/// shared object with an interpreter is taken for a PIE when its dynamic
+/// section is out of reach.
+pub(crate) fn identify(mut data: &[u8]) -> Option<Classification> {
+    classify(parse(&mut data)?)
+}
+
+/// Like [`identify`], reading the dynamic section and section headers from
+/// wherever they lie in `reader`.
+pub(crate) fn identify_seekable(reader: impl Read + Seek) -> Option<Classification> {
+    classify(parse(&mut Seekable::new(reader)?)?)
+}
+
+fn classify(elf: Elf) -> Option<Classification> {
+    let mime_type = match elf.kind {
+        Kind::Relocatable => "application/x-object",
+        Kind::Executable => "application/x-executable",
+        Kind::Shared if elf.pie => "application/x-pie-executable",
+        Kind::Shared => "application/x-sharedlib",
+        Kind::Core => "application/x-coredump",
+        Kind::Other(_) => "application/octet-stream",
+    };
+    let mut classification = Classification::binary(BufferType::Elf, elf.description(), mime_type);
+    classification.extensions = match elf.kind {
+        Kind::Relocatable => vec![String::from("o")],
+        Kind::Shared if !elf.pie => vec![String::from("so")],
+        _ => Vec::new(),
+    };
+    Some(classification)
+}
+
+/// Runs [`identify`] on files with the ELF ident.
+pub(crate) struct ElfDetector;
+
+impl Detector for ElfDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        head.starts_with(b"\x7fELF")
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+fn parse(source: &mut impl Source) -> Option<Elf> {
+    let ident = source.read(0, 16)?;
+    if !ident.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let wide = match ident[4] {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let big_endian = match ident[5] {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let (version, os_abi) = (ident[6], ident[7]);
+    let data = source.read(0, if wide { 64 } else { 52 })?;
+    let bytes = Bytes::new(&data, big_endian);
+    let kind = match bytes.u16(16)? {
+        1 => Kind::Relocatable,
+        2 => Kind::Executable,
+        3 => Kind::Shared,
+        4 => Kind::Core,
+        other => Kind::Other(other),
+    };
+    let header = if wide {
+        Header {
+            program_offset: bytes.u64(32)?,
+            section_offset: bytes.u64(40)?,
+            program_size: bytes.u16(54)?,
+            program_count: bytes.u16(56)?,
+            section_size: bytes.u16(58)?,
+            section_count: bytes.u16(60)?,
+        }
+    } else {
+        Header {
+            program_offset: bytes.u32(28)? as u64,
+            section_offset: bytes.u32(32)? as u64,
+            program_size: bytes.u16(42)?,
+            program_count: bytes.u16(44)?,
+            section_size: bytes.u16(46)?,
+            section_count: bytes.u16(48)?,
+        }
+    };
+    let mut elf = Elf {
+        wide,
+        big_endian,
+        kind,
+        machine: bytes.u16(18)?,
+        version,
+        os_abi,
+        interpreter: None,
+        dynamic: false,
+        pie: false,
+        build_id: None,
+        abi_tag: None,
+        symbols: None,
+        sections: header.section_count,
+    };
+    // the flags of the dynamic section, or `None` when it is out of reach
+    let mut dynamic_flags = Some(0);
+    for segment in header.segments(source, wide, big_endian) {
+        match segment.kind {
+            PT_INTERP => {
+                elf.interpreter = segment.contents(source).map(|path| {
+                    let end = path
+                        .iter()
+                        .position(|&byte| byte == 0)
+                        .unwrap_or(path.len());
+                    String::from_utf8_lossy(&path[..end]).into_owned()
+                });
+            }
+            PT_DYNAMIC => {
+                elf.dynamic = true;
+                dynamic_flags = segment
+                    .contents(source)
+                    .map(|contents| flags_1(Bytes::new(&contents, big_endian), wide));
+            }
+            PT_NOTE => {
+                if let Some(contents) = segment.contents(source) {
+                    elf.read_notes(Bytes::new(&contents, big_endian), segment.align);
+                }
+            }
+            _ => {}
+        }
+    }
+    elf.pie = match dynamic_flags {
+        Some(flags) => flags & DF_1_PIE != 0,
+        None => elf.interpreter.is_some(),
+    };
+    elf.symbols = header.has_symbol_table(source, big_endian);
+    Some(elf)
+}
+
+struct Header {
+    program_offset: u64,
+    section_offset: u64,
+    program_size: u16,
+    program_count: u16,
+    section_size: u16,
+    section_count: u16,
+}
+
+struct Segment {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    align: u64,
+}
+
+impl Segment {
+    /// The contents of the segment, up to [`SEGMENT_MAX`] bytes of it.
+    fn contents<'a>(&self, source: &'a mut impl Source) -> Option<Cow<'a, [u8]>> {
+        source.read(self.offset, self.size.min(SEGMENT_MAX))
+    }
+}
+
+impl Header {
+    /// The program headers up to the first one that cannot be read.
+    fn segments(&self, source: &mut impl Source, wide: bool, big_endian: bool) -> Vec<Segment> {
+        let minimum = if wide { 56 } else { 32 };
+        let (offset, size) = (self.program_offset, self.program_size as u64);
+        let count = if size < minimum {
+            0
+        } else {
+            self.program_count
+        };
+        (0..count as u64)
+            .map_while(|index| {
+                let at = offset.checked_add(index * size)?;
+                let entry = source.read(at, minimum)?;
+                let bytes = Bytes::new(&entry, big_endian);
+                Some(if wide {
+                    Segment {
+                        kind: bytes.u32(0)?,
+                        offset: bytes.u64(8)?,
+                        size: bytes.u64(32)?,
+                        align: bytes.u64(48)?,
+                    }
+                } else {
+                    Segment {
+                        kind: bytes.u32(0)?,
+                        offset: bytes.u32(4)? as u64,
+                        size: bytes.u32(16)? as u64,
+                        align: bytes.u32(28)? as u64,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Whether any section is a symbol table, or `None` when the section
+    /// headers cannot be read.
+    fn has_symbol_table(&self, source: &mut impl Source, big_endian: bool) -> Option<bool> {
+        if self.section_count == 0 || self.section_size < 8 {
+            return None;
+        }
+        let size = self.section_size as u64;
+        let table = source.read(self.section_offset, self.section_count as u64 * size)?;
+        let bytes = Bytes::new(&table, big_endian);
+        let found = (0..self.section_count as u64)
+            .any(|index| bytes.u32(index * size + 4) == Some(SHT_SYMTAB));
+        Some(found)
+    }
+}
+
+/// Finds `DT_FLAGS_1` in a dynamic section.
+fn flags_1(dynamic: Bytes, wide: bool) -> u64 {
+    let size = if wide { 16 } else { 8 };
+    let mut at = 0;
+    loop {
+        let entry = if wide {
+            dynamic.u64(at).zip(dynamic.u64(at + 8))
+        } else {
+            dynamic
+                .u32(at)
+                .zip(dynamic.u32(at + 4))
+                .map(|(tag, value)| (tag as u64, value as u64))
+        };
+        match entry {
+            Some((DT_FLAGS_1, value)) => return value,
+            Some((DT_NULL, _)) | None => return 0,
+            Some(_) => at += size,
+        }
+    }
+}
+
+impl Elf {
+    fn read_notes(&mut self, notes: Bytes, align: u64) {
+        let align = if align == 8 { 8 } else { 4 };
+        let round = |value: u64| value.div_ceil(align) * align;
+        let mut at = 0;
+        while let (Some(name_size), Some(desc_size), Some(kind)) =
+            (notes.u32(at), notes.u32(at + 4), notes.u32(at + 8))
+        {
+            let name_at = at + 12;
+            let desc_at = name_at + round(name_size as u64);
+            let (Some(name), Some(desc)) = (
+                notes.slice(name_at, name_size as u64),
+                notes.slice(desc_at, desc_size as u64),
+            ) else {
+                return;
+            };
+            if name == b"GNU\0" {
+                match kind {
+                    NT_GNU_BUILD_ID => self.build_id = Some(desc.to_vec()),
+                    NT_GNU_ABI_TAG if desc.len() >= 16 => {
+                        let desc = Bytes::new(desc, self.big_endian);
+                        self.abi_tag = Some((
+                            desc.u32(0).unwrap_or_default(),
+                            [4, 8, 12].map(|at| desc.u32(at).unwrap_or_default()),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            at = desc_at + round(desc_size as u64);
+        }
+    }
+
+    /// Describes the file the way GNU file does.
+    fn description(&self) -> String {
+        let mut description = format!(
+            "ELF {}-bit {} {}, {}, version {} ({})",
+            if self.wide { 64 } else { 32 },
+            if self.big_endian { "MSB" } else { "LSB" },
+            match self.kind {
+                Kind::Relocatable => "relocatable".to_owned(),
+                Kind::Executable => "executable".to_owned(),
+                Kind::Shared if self.pie => "pie executable".to_owned(),
+                Kind::Shared => "shared object".to_owned(),
+                Kind::Core => "core file".to_owned(),
+                Kind::Other(kind) => format!("unknown type {kind:#x}"),
+            },
+            machine(self.machine),
+            self.version,
+            os_abi(self.os_abi),
+        );
+        if matches!(self.kind, Kind::Executable | Kind::Shared) {
+            description.push_str(match (self.dynamic, &self.interpreter) {
+                (_, Some(_)) => ", dynamically linked",
+                (true, None) if self.pie => ", static-pie linked",
+                (true, None) => ", dynamically linked",
+                (false, None) => ", statically linked",
+            });
+        }
+        if let Some(interpreter) = &self.interpreter {
+            let _ = write!(description, ", interpreter {interpreter}");
+        }
+        if let Some(build_id) = &self.build_id {
+            let hash = match build_id.len() {
+                8 => "xxHash",
+                16 => "md5/uuid",
+                20 => "sha1",
+                _ => "unknown",
+            };
+            let _ = write!(description, ", BuildID[{hash}]=");
+            for byte in build_id {
+                let _ = write!(description, "{byte:02x}");
+            }
+        }
+        if let Some((os, [major, minor, patch])) = self.abi_tag {
+            let os = match os {
+                0 => "GNU/Linux",
+                1 => "GNU/Hurd",
+                2 => "Solaris",
+                3 => "FreeBSD",
+                _ => "unknown OS",
+            };
+            let _ = write!(description, ", for {os} {major}.{minor}.{patch}");
+        }
+        match self.symbols {
+            _ if self.sections == 0 && self.kind != Kind::Core => {
+                description.push_str(", no section header");
+            }
+            Some(true) => description.push_str(", not stripped"),
+            Some(false) => description.push_str(", stripped"),
+            None => {}
+        }
+        description
+    }
+}
+
+fn machine(machine: u16) -> String {
+    let name = match machine {
+        2 => "SPARC",
+        3 => "Intel 80386",
+        4 => "Motorola m68k",
+        8 => "MIPS",
+        20 => "PowerPC",
+        21 => "64-bit PowerPC",
+        22 => "IBM S/390",
+        40 => "ARM",
+        42 => "Renesas SH",
+        43 => "SPARC V9",
+        50 => "IA-64",
+        62 => "x86-64",
+        183 => "ARM aarch64",
+        243 => "UCB RISC-V",
+        247 => "eBPF",
+        258 => "LoongArch",
+        _ => return format!("unknown machine {machine}"),
+    };
+    name.to_owned()
+}
+
+fn os_abi(os_abi: u8) -> String {
+    let name = match os_abi {
+        0 => "SYSV",
+        1 => "HP-UX",
+        2 => "NetBSD",
+        3 => "GNU/Linux",
+        6 => "Solaris",
+        7 => "AIX",
+        8 => "IRIX",
+        9 => "FreeBSD",
+        10 => "Tru64",
+        12 => "OpenBSD",
+        97 => "ARM",
+        255 => "embedded",
+        _ => return format!("OS ABI {os_abi}"),
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a little-endian x86-64 ELF with an interpreter, a build-id and
+    /// ABI tag note, a PIE dynamic section and a symbol table.
+    fn sample(kind: u16) -> Vec<u8> {
+        let mut elf = vec![0; 0x300];
+        elf[..8].copy_from_slice(b"\x7fELF\x02\x01\x01\x00");
+        elf[16..18].copy_from_slice(&kind.to_le_bytes());
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes());
+        elf[32..40].copy_from_slice(&64u64.to_le_bytes());
+        elf[40..48].copy_from_slice(&0x200u64.to_le_bytes());
+        elf[54..56].copy_from_slice(&56u16.to_le_bytes());
+        elf[56..58].copy_from_slice(&3u16.to_le_bytes());
+        elf[58..60].copy_from_slice(&64u16.to_le_bytes());
+        elf[60..62].copy_from_slice(&2u16.to_le_bytes());
+        let segments: [(u32, u64, u64); 3] = [
+            (PT_INTERP, 0x100, 28),
+            (PT_NOTE, 0x120, 68),
+            (PT_DYNAMIC, 0x170, 32),
+        ];
+        for (index, (kind, offset, size)) in segments.into_iter().enumerate() {
+            let at = 64 + index * 56;
+            elf[at..at + 4].copy_from_slice(&kind.to_le_bytes());
+            elf[at + 8..at + 16].copy_from_slice(&offset.to_le_bytes());
+            elf[at + 32..at + 40].copy_from_slice(&size.to_le_bytes());
+            elf[at + 48..at + 56].copy_from_slice(&4u64.to_le_bytes());
+        }
+        elf[0x100..0x11C].copy_from_slice(b"/lib64/ld-linux-x86-64.so.2\0");
+        let mut notes = Vec::new();
+        for (kind, desc) in [
+            (
+                NT_GNU_ABI_TAG,
+                [0u32, 3, 2, 0].map(u32::to_le_bytes).concat(),
+            ),
+            (NT_GNU_BUILD_ID, (0..20).collect()),
+        ] {
+            notes.extend(4u32.to_le_bytes());
+            notes.extend((desc.len() as u32).to_le_bytes());
+            notes.extend(kind.to_le_bytes());
+            notes.extend(b"GNU\0");
+            notes.extend(desc);
+        }
+        elf[0x120..0x120 + notes.len()].copy_from_slice(&notes);
+        elf[0x170..0x178].copy_from_slice(&DT_FLAGS_1.to_le_bytes());
+        elf[0x178..0x180].copy_from_slice(&DF_1_PIE.to_le_bytes());
+        elf[0x244..0x248].copy_from_slice(&SHT_SYMTAB.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn test_identify() {
+        let pie = identify(&sample(3)).unwrap();
+        assert_eq!(
+            pie.description,
+            "ELF 64-bit LSB pie executable, x86-64, version 1 (SYSV), dynamically linked, \
+             interpreter /lib64/ld-linux-x86-64.so.2, \
+             BuildID[sha1]=000102030405060708090a0b0c0d0e0f10111213, for GNU/Linux 3.2.0, \
+             not stripped"
+        );
+        assert_eq!(pie.mime_type, "application/x-pie-executable");
+        let mut truncated = sample(2);
+        truncated.truncate(0x200);
+        let executable = identify(&truncated).unwrap();
+        assert!(executable
+            .description
+            .starts_with("ELF 64-bit LSB executable, x86-64"));
+        assert!(executable.description.ends_with("3.2.0"));
+        assert!(identify(b"\x7fELF\x03").is_none());
+        assert!(identify(b"MZ").is_none());
+    }
+
+    #[test]
+    fn test_far_tables() {
+        // a shared library with an interpreter, like libc, whose dynamic
+        // section and section headers follow more than 1 MiB of code
+        let mut library = sample(3);
+        let far = 0x18_0000;
+        library.resize(far + 0x100, 0);
+        library[far..far + 32].copy_from_slice(&[&DT_NULL.to_le_bytes()[..], &[0; 24]].concat());
+        library[64 + 2 * 56 + 8..64 + 2 * 56 + 16].copy_from_slice(&(far as u64).to_le_bytes());
+        library.copy_within(0x200..0x280, far + 0x80);
+        library[40..48].copy_from_slice(&(far as u64 + 0x80).to_le_bytes());
+        let head = &library[..0x10_0000];
+        let guessed = identify(head).unwrap();
+        assert!(guessed
+            .description
+            .starts_with("ELF 64-bit LSB pie executable"));
+        assert!(guessed.description.ends_with("3.2.0"));
+        let seeked = identify_seekable(std::io::Cursor::new(&library)).unwrap();
+        assert!(seeked
+            .description
+            .starts_with("ELF 64-bit LSB shared object"));
+        assert!(seeked.description.ends_with("3.2.0, not stripped"));
+        assert_eq!(seeked.mime_type, "application/x-sharedlib");
+    }
+
+    #[test]
+    fn test_flags_1() {
+        let dynamic = [
+            &1u64.to_le_bytes()[..],
+            &5u64.to_le_bytes(),
+            &DT_FLAGS_1.to_le_bytes(),
+            &DF_1_PIE.to_le_bytes(),
+        ]
+        .concat();
+        assert_eq!(flags_1(Bytes::new(&dynamic, false), true), DF_1_PIE);
+        assert_eq!(flags_1(Bytes::new(&dynamic[..16], false), true), 0);
+    }
+}
@@ -0,0 +1,734 @@
+use std::fmt::Write;
+
+use crate::{
+    formats::{starts_with_any, Bytes},
+    BufferType, Classification, Detector,
+};
+
+/// The leading bytes of every format but HEIF, whose `ftyp` box comes after
+/// its size.
+const SIGNATURES: &[&[u8]] = &[
+    b"\x89PNG",
+    b"\xff\xd8\xff",
+    b"GIF8",
+    b"RIFF",
+    b"II*\0",
+    b"MM\0*",
+    b"II+\0",
+    b"MM\0+",
+    b"BM",
+    b"qoif",
+    b"\0\0\x01\0",
+    b"\0\0\x02\0",
+];
+
+/// What one of the image parsers found.
+struct Image {
+    description: String,
+    mime_type: &'static str,
+    extensions: &'static [&'static str],
+}
+
+/// Recognises the common raster image formats, describing their dimensions,
+/// bit depth and colour type where the header gives them.
+pub(crate) fn identify(data: &[u8]) -> Option<Classification> {
+    let image = png(data)
+        .or_else(|| jpeg(data))
+        .or_else(|| gif(data))
+        .or_else(|| webp(data))
+        .or_else(|| tiff(data))
+        .or_else(|| bmp(data))
+        .or_else(|| qoi(data))
+        .or_else(|| heif(data))
+        .or_else(|| icon(data))?;
+    let mut classification =
+        Classification::binary(BufferType::Image, image.description, image.mime_type);
+    classification.extensions = image.extensions.iter().map(|&e| e.to_owned()).collect();
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with an image signature.
+pub(crate) struct ImageDetector;
+
+impl Detector for ImageDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        starts_with_any(head, SIGNATURES) || head.get(4..8) == Some(b"ftyp")
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+fn png(data: &[u8]) -> Option<Image> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let bytes = Bytes::new(data, true);
+    let depth = *data.get(24)?;
+    let colour = match *data.get(25)? {
+        0 => format!("{depth}-bit grayscale"),
+        2 => format!("{depth}-bit/color RGB"),
+        3 => format!("{depth}-bit colormap"),
+        4 => format!("{depth}-bit gray+alpha"),
+        6 => format!("{depth}-bit/color RGBA"),
+        _ => return None,
+    };
+    let interlace = if *data.get(28)? == 0 {
+        "non-interlaced"
+    } else {
+        "interlaced"
+    };
+    Some(Image {
+        description: format!(
+            "PNG image data, {} x {}, {colour}, {interlace}",
+            bytes.u32(16)?,
+            bytes.u32(20)?
+        ),
+        mime_type: "image/png",
+        extensions: &["png"],
+    })
+}
+
+/// Walks the JPEG markers up to the first frame header, which holds the
+/// coding process, precision, dimensions and component count.
+fn jpeg(data: &[u8]) -> Option<Image> {
+    if !data.starts_with(b"\xff\xd8\xff") {
+        return None;
+    }
+    let bytes = Bytes::new(data, true);
+    let mut description = String::from("JPEG image data");
+    let mut at = 2;
+    while let (Some(0xFF), Some(&marker)) = (data.get(at), data.get(at + 1)) {
+        if marker == 0xFF {
+            at += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            at += 2;
+            continue;
+        }
+        let Some(length) = bytes.u16(at as u64 + 2) else {
+            break;
+        };
+        let segment = data.get(at + 4..(at + 2 + length as usize).min(data.len()));
+        match marker {
+            0xE0 => {
+                if let Some([b'J', b'F', b'I', b'F', 0, major, minor, ..]) = segment {
+                    let _ = write!(description, ", JFIF standard {major}.{minor:02}");
+                }
+            }
+            0xE1 if segment.is_some_and(|segment| segment.starts_with(b"Exif\0")) => {
+                description.push_str(", Exif standard");
+            }
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let process = match marker & 0x07 {
+                    0 => "baseline",
+                    1 => "extended sequential",
+                    2 => "progressive",
+                    3 => "lossless",
+                    5 => "differential sequential",
+                    6 => "differential progressive",
+                    _ => "differential lossless",
+                };
+                if let Some(
+                    &[precision, height_high, height_low, width_high, width_low, components, ..],
+                ) = segment
+                {
+                    let height = u16::from_be_bytes([height_high, height_low]);
+                    let width = u16::from_be_bytes([width_high, width_low]);
+                    let _ = write!(
+                        description,
+                        ", {process}, precision {precision}, {width}x{height}, components {components}"
+                    );
+                }
+                break;
+            }
+            0xDA | 0xD9 => break,
+            _ => {}
+        }
+        at += 2 + length as usize;
+    }
+    Some(Image {
+        description,
+        mime_type: "image/jpeg",
+        extensions: &["jpeg", "jpg", "jpe", "jfif"],
+    })
+}
+
+fn gif(data: &[u8]) -> Option<Image> {
+    let version = match data.get(..6)? {
+        b"GIF87a" => "87a",
+        b"GIF89a" => "89a",
+        _ => return None,
+    };
+    let bytes = Bytes::new(data, false);
+    let mut description = format!(
+        "GIF image data, version {version}, {} x {}",
+        bytes.u16(6)?,
+        bytes.u16(8)?
+    );
+    let flags = *data.get(10)?;
+    if flags & 0x80 != 0 {
+        let _ = write!(description, ", {}-bit colormap", (flags & 0x07) + 1);
+    }
+    Some(Image {
+        description,
+        mime_type: "image/gif",
+        extensions: &["gif"],
+    })
+}
+
+fn webp(data: &[u8]) -> Option<Image> {
+    if !data.starts_with(b"RIFF") || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let bytes = Bytes::new(data, false);
+    let mut encoding = None;
+    let mut size = None;
+    let mut alpha = false;
+    let mut animated = false;
+    let mut at = 12;
+    while let (Some(kind), Some(length)) = (bytes.slice(at, 4), bytes.u32(at + 4)) {
+        let contents = at + 8;
+        match kind {
+            b"VP8 " => {
+                encoding.get_or_insert("lossy");
+                if bytes.slice(contents + 3, 3) == Some(b"\x9d\x01\x2a") {
+                    size = size
+                        .or(bytes.u16(contents + 6).zip(bytes.u16(contents + 8)).map(
+                            |(width, height)| (width as u32 & 0x3FFF, height as u32 & 0x3FFF),
+                        ));
+                }
+            }
+            b"VP8L" => {
+                encoding.get_or_insert("lossless");
+                if let (None, Some(0x2F), Some(bits)) =
+                    (size, data.get(contents as usize), bytes.u32(contents + 1))
+                {
+                    alpha |= bits >> 28 & 1 != 0;
+                    size = Some(((bits & 0x3FFF) + 1, (bits >> 14 & 0x3FFF) + 1));
+                }
+            }
+            b"VP8X" => {
+                if let Some(&[flags, _, _, _, w0, w1, w2, h0, h1, h2]) = bytes.slice(contents, 10) {
+                    alpha = flags & 0x10 != 0;
+                    animated = flags & 0x02 != 0;
+                    size = Some((
+                        u32::from_le_bytes([w0, w1, w2, 0]) + 1,
+                        u32::from_le_bytes([h0, h1, h2, 0]) + 1,
+                    ));
+                }
+            }
+            b"ANIM" | b"ANMF" => animated = true,
+            _ => {}
+        }
+        if animated && size.is_some() {
+            break;
+        }
+        let Some(next) = contents.checked_add((length as u64).next_multiple_of(2)) else {
+            break;
+        };
+        at = next;
+    }
+    let mut description = String::from("RIFF (little-endian) data, Web/P image");
+    if animated {
+        description.push_str(", animated");
+    } else if let Some(encoding) = encoding {
+        let _ = write!(description, ", {encoding}");
+    }
+    if alpha {
+        description.push_str(", with alpha");
+    }
+    if let Some((width, height)) = size {
+        let _ = write!(description, ", {width}x{height}");
+    }
+    Some(Image {
+        description,
+        mime_type: "image/webp",
+        extensions: &["webp"],
+    })
+}
+
+const TIFF_WIDTH: u16 = 256;
+const TIFF_HEIGHT: u16 = 257;
+const TIFF_BITS_PER_SAMPLE: u16 = 258;
+const TIFF_COMPRESSION: u16 = 259;
+const TIFF_PHOTOMETRIC: u16 = 262;
+
+/// Reads the first image directory of a TIFF or BigTIFF file.
+fn tiff(data: &[u8]) -> Option<Image> {
+    let (big_endian, wide) = match data.get(..4)? {
+        b"II*\0" => (false, false),
+        b"MM\0*" => (true, false),
+        b"II+\0" => (false, true),
+        b"MM\0+" => (true, true),
+        _ => return None,
+    };
+    let bytes = Bytes::new(data, big_endian);
+    let mut description = format!(
+        "{}TIFF image data, {}-endian",
+        if wide { "Big " } else { "" },
+        if big_endian { "big" } else { "little" }
+    );
+    let directory = if wide {
+        bytes.u64(8)
+    } else {
+        bytes.u32(4).map(u64::from)
+    };
+    let (count, entries) = match directory {
+        Some(at) if wide => (bytes.u64(at), at.checked_add(8)),
+        Some(at) => (bytes.u16(at).map(u64::from), at.checked_add(2)),
+        None => (None, None),
+    };
+    let (Some(count), Some(entries)) = (count, entries) else {
+        return Some(tiff_image(description));
+    };
+    let _ = write!(description, ", direntries={count}");
+    let entry_size = if wide { 20 } else { 12 };
+    let mut fields = [None; 5];
+    for index in 0..count.min(1024) {
+        let Some(at) = index
+            .checked_mul(entry_size)
+            .and_then(|offset| entries.checked_add(offset))
+        else {
+            break;
+        };
+        let Some(tag) = bytes.u16(at) else {
+            break;
+        };
+        let slot = match tag {
+            TIFF_WIDTH => 0,
+            TIFF_HEIGHT => 1,
+            TIFF_BITS_PER_SAMPLE => 2,
+            TIFF_COMPRESSION => 3,
+            TIFF_PHOTOMETRIC => 4,
+            _ => continue,
+        };
+        fields[slot] = tiff_value(bytes, at, wide);
+    }
+    let [width, height, bits, compression, photometric] = fields;
+    if let Some(width) = width {
+        let _ = write!(description, ", width={width}");
+    }
+    if let Some(height) = height {
+        let _ = write!(description, ", height={height}");
+    }
+    if let Some(bits) = bits {
+        let _ = write!(description, ", bps={bits}");
+    }
+    if let Some(compression) = compression {
+        let name = match compression {
+            1 => "none",
+            2 => "huffman",
+            3 => "bi-level group 3",
+            4 => "bi-level group 4",
+            5 => "LZW",
+            6 => "JPEG (old)",
+            7 => "JPEG",
+            8 | 32946 => "deflate",
+            32773 => "PackBits",
+            _ => "",
+        };
+        if name.is_empty() {
+            let _ = write!(description, ", compression={compression}");
+        } else {
+            let _ = write!(description, ", compression={name}");
+        }
+    }
+    if let Some(photometric) = photometric {
+        let name = match photometric {
+            0 => "WhiteIsZero",
+            1 => "BlackIsZero",
+            2 => "RGB",
+            3 => "RGB Palette",
+            4 => "Transparency Mask",
+            5 => "CMYK",
+            6 => "YCbCr",
+            8 => "CIELab",
+            _ => "",
+        };
+        if name.is_empty() {
+            let _ = write!(description, ", PhotometricInterpretation={photometric}");
+        } else {
+            let _ = write!(description, ", PhotometricInterpretation={name}");
+        }
+    }
+    Some(tiff_image(description))
+}
+
+fn tiff_image(description: String) -> Image {
+    Image {
+        description,
+        mime_type: "image/tiff",
+        extensions: &["tif", "tiff"],
+    }
+}
+
+/// The first value of a directory entry, stored inline when it fits and at
+/// an offset otherwise.
+fn tiff_value(bytes: Bytes, entry: u64, wide: bool) -> Option<u64> {
+    let size = match bytes.u16(entry + 2)? {
+        3 => 2,
+        4 => 4,
+        16 => 8,
+        _ => return None,
+    };
+    let (count, value, inline) = if wide {
+        (bytes.u64(entry + 4)?, entry + 12, 8)
+    } else {
+        (bytes.u32(entry + 4)? as u64, entry + 8, 4)
+    };
+    let at = if count.checked_mul(size)? <= inline {
+        value
+    } else if wide {
+        bytes.u64(value)?
+    } else {
+        bytes.u32(value)? as u64
+    };
+    match size {
+        2 => bytes.u16(at).map(u64::from),
+        4 => bytes.u32(at).map(u64::from),
+        _ => bytes.u64(at),
+    }
+}
+
+fn bmp(data: &[u8]) -> Option<Image> {
+    if !data.starts_with(b"BM") {
+        return None;
+    }
+    let bytes = Bytes::new(data, false);
+    let header = bytes.u32(14)?;
+    let format = match header {
+        12 => "OS/2 1.x format",
+        64 => "OS/2 2.x format",
+        40 | 52 | 56 => "Windows 3.x format",
+        108 => "Windows 95/NT4 and newer format",
+        124 => "Windows 98/2000 and newer format",
+        _ => return None,
+    };
+    let (width, height, planes, bits) = if header == 12 {
+        (
+            bytes.u16(18)? as i32,
+            bytes.u16(20)? as i32,
+            bytes.u16(22)?,
+            bytes.u16(24)?,
+        )
+    } else {
+        (
+            bytes.u32(18)? as i32,
+            bytes.u32(22)? as i32,
+            bytes.u16(26)?,
+            bytes.u16(28)?,
+        )
+    };
+    if planes != 1 {
+        return None;
+    }
+    Some(Image {
+        description: format!("PC bitmap, {format}, {width} x {height} x {bits}"),
+        mime_type: "image/bmp",
+        extensions: &["bmp"],
+    })
+}
+
+fn qoi(data: &[u8]) -> Option<Image> {
+    if !data.starts_with(b"qoif") {
+        return None;
+    }
+    let bytes = Bytes::new(data, true);
+    let channels = match *data.get(12)? {
+        3 => "RGB",
+        4 => "RGBA",
+        _ => return None,
+    };
+    let colour_space = match *data.get(13)? {
+        0 => "sRGB with linear alpha",
+        1 => "all channels linear",
+        _ => return None,
+    };
+    Some(Image {
+        description: format!(
+            "QOI image data, {} x {}, {channels}, {colour_space}",
+            bytes.u32(4)?,
+            bytes.u32(8)?
+        ),
+        mime_type: "image/x-qoi",
+        extensions: &["qoi"],
+    })
+}
+
+/// Recognises AVIF and HEIC from the `ftyp` brands of an ISO media file, then
+/// reads the first spatial extent and pixel information properties.
+fn heif(data: &[u8]) -> Option<Image> {
+    if data.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let bytes = Bytes::new(data, true);
+    let ftyp_end = (bytes.u32(0)? as usize).min(data.len());
+    let major = data.get(8..12)?;
+    let compatible = data.get(16..ftyp_end).unwrap_or_default();
+    let has = |brand: &[u8]| major == brand || compatible.chunks(4).any(|b| b == brand);
+    let (kind, mime_type, extensions): (_, _, &[_]) = if has(b"avis") {
+        ("AVIF Image Sequence", "image/avif", &["avifs", "avif"])
+    } else if has(b"avif") {
+        ("AVIF Image", "image/avif", &["avif"])
+    } else if has(b"hevc") || has(b"hevx") {
+        (
+            "HEIC Image Sequence",
+            "image/heic-sequence",
+            &["heics", "heic"],
+        )
+    } else if has(b"heic") || has(b"heix") {
+        ("HEIC Image", "image/heic", &["heic"])
+    } else if has(b"mif1") || has(b"msf1") {
+        ("HEIF Image", "image/heif", &["heif"])
+    } else {
+        return None;
+    };
+    let mut description = format!("ISO Media, {kind}");
+    let properties = child(bytes, 0, data.len() as u64, b"meta")
+        .and_then(|(start, end)| child(bytes, start + 4, end, b"iprp"))
+        .and_then(|(start, end)| child(bytes, start, end, b"ipco"));
+    if let Some((start, end)) = properties {
+        if let Some((at, _)) = child(bytes, start, end, b"ispe") {
+            let _ = write!(
+                description,
+                ", {} x {}",
+                bytes.u32(at + 4)?,
+                bytes.u32(at + 8)?
+            );
+        }
+        if let Some((at, _)) = child(bytes, start, end, b"pixi") {
+            let channels = *data.get(at as usize + 4)?;
+            let bits = *data.get(at as usize + 5)?;
+            let _ = write!(
+                description,
+                ", {bits}-bit, {channels} channel{}",
+                if channels == 1 { "" } else { "s" }
+            );
+        }
+    }
+    Some(Image {
+        description,
+        mime_type,
+        extensions,
+    })
+}
+
+/// Finds the first box of `kind` between `start` and `end`, returning the
+/// range of its contents. Box sizes come from the file, so a size that
+/// overflows the offset ends the search.
+fn child(bytes: Bytes, start: u64, end: u64, kind: &[u8]) -> Option<(u64, u64)> {
+    let mut at = start;
+    while at.checked_add(8)? <= end {
+        let size = match bytes.u32(at)? as u64 {
+            0 => end - at,
+            1 => bytes.u64(at + 8)?,
+            size => size,
+        };
+        let header = if bytes.u32(at)? == 1 { 16 } else { 8 };
+        if size < header {
+            return None;
+        }
+        if bytes.slice(at + 4, 4)? == kind {
+            return Some((at + header, at.checked_add(size)?.min(end)));
+        }
+        at = at.checked_add(size)?;
+    }
+    None
+}
+
+/// Windows icon and cursor resources, which only have a directory to go by.
+fn icon(data: &[u8]) -> Option<Image> {
+    let cursor = match data.get(..4)? {
+        b"\0\0\x01\0" => false,
+        b"\0\0\x02\0" => true,
+        _ => return None,
+    };
+    let bytes = Bytes::new(data, false);
+    let count = bytes.u16(4)?;
+    let directory_end = 6 + count as u32 * 16;
+    if count == 0 {
+        return None;
+    }
+    let mut entries = Vec::new();
+    for index in 0..count as u64 {
+        let at = 6 + index * 16;
+        let Some(entry) = bytes.slice(at, 16) else {
+            break;
+        };
+        let (planes, bits) = (bytes.u16(at + 4)?, bytes.u16(at + 6)?);
+        if entry[3] != 0 || bytes.u32(at + 8)? == 0 || bytes.u32(at + 12)? < directory_end {
+            return None;
+        }
+        if !cursor && planes > 1 {
+            return None;
+        }
+        let side = |side: u8| if side == 0 { 256 } else { side as u32 };
+        let mut entry = format!("{}x{}", side(entry[0]), side(entry[1]));
+        if cursor {
+            let _ = write!(entry, ", hotspot @{planes}x{bits}");
+        } else if bits != 0 {
+            let _ = write!(entry, ", {bits} bits/pixel");
+        }
+        entries.push(entry);
+    }
+    let (kind, mime_type, extensions): (_, _, &[_]) = if cursor {
+        ("cursor", "image/x-win-bitmap", &["cur"])
+    } else {
+        ("icon", "image/vnd.microsoft.icon", &["ico"])
+    };
+    Some(Image {
+        description: format!(
+            "MS Windows {kind} resource - {count} icon{}, {}",
+            if count == 1 { "" } else { "s" },
+            entries.join(", ")
+        ),
+        mime_type,
+        extensions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(data: &[u8]) -> String {
+        identify(data).unwrap().description
+    }
+
+    #[test]
+    fn test_png_jpeg_gif() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        let classification = identify(&png).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Image);
+        assert_eq!(classification.mime_type, "image/png");
+        assert_eq!(
+            classification.description,
+            "PNG image data, 640 x 480, 8-bit/color RGBA, non-interlaced"
+        );
+
+        let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01\0\x01\0\0\
+                     \xff\xc2\0\x11\x08\x01\xe0\x02\x80\x03";
+        assert_eq!(
+            describe(jpeg),
+            "JPEG image data, JFIF standard 1.01, progressive, precision 8, 640x480, components 3"
+        );
+
+        assert_eq!(
+            describe(b"GIF89a\x10\0\x20\0\xf7\0\0"),
+            "GIF image data, version 89a, 16 x 32, 8-bit colormap"
+        );
+    }
+
+    #[test]
+    fn test_webp() {
+        let mut lossy = b"RIFF\0\0\0\0WEBPVP8 \x10\0\0\0\0\0\0\x9d\x01\x2a".to_vec();
+        lossy.extend(640u16.to_le_bytes());
+        lossy.extend(480u16.to_le_bytes());
+        assert_eq!(
+            describe(&lossy),
+            "RIFF (little-endian) data, Web/P image, lossy, 640x480"
+        );
+
+        let bits: u32 = 99 | 49 << 14 | 1 << 28;
+        let mut lossless = b"RIFF\0\0\0\0WEBPVP8L\x05\0\0\0\x2f".to_vec();
+        lossless.extend(bits.to_le_bytes());
+        assert_eq!(
+            describe(&lossless),
+            "RIFF (little-endian) data, Web/P image, lossless, with alpha, 100x50"
+        );
+
+        let animated = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x12\0\0\0\x3f\x01\0\xdf\x01\0";
+        assert_eq!(
+            describe(animated),
+            "RIFF (little-endian) data, Web/P image, animated, with alpha, 320x480"
+        );
+    }
+
+    #[test]
+    fn test_tiff_bmp_qoi() {
+        let mut tiff = b"II*\0\x08\0\0\0\x04\0".to_vec();
+        for (tag, kind, value) in [
+            (256u16, 3u16, 640u32),
+            (257, 4, 480),
+            (258, 3, 8),
+            (262, 3, 2),
+        ] {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(kind.to_le_bytes());
+            tiff.extend(1u32.to_le_bytes());
+            tiff.extend(value.to_le_bytes());
+        }
+        assert_eq!(
+            describe(&tiff),
+            "TIFF image data, little-endian, direntries=4, width=640, height=480, bps=8, \
+             PhotometricInterpretation=RGB"
+        );
+
+        let mut bmp = b"BM\0\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0".to_vec();
+        bmp.extend(640u32.to_le_bytes());
+        bmp.extend((-480i32).to_le_bytes());
+        bmp.extend([1, 0, 24, 0]);
+        assert_eq!(
+            describe(&bmp),
+            "PC bitmap, Windows 3.x format, 640 x -480 x 24"
+        );
+        assert!(identify(b"BM\0\0\0\0\0\0\0\0\0\0\0\0\x07\0\0\0").is_none());
+
+        // a directory offset that overflows once the entry count is skipped
+        let big = [&b"II+\0\x08\0\0\0"[..], &(u64::MAX - 4).to_le_bytes()].concat();
+        assert_eq!(describe(&big), "Big TIFF image data, little-endian");
+
+        let qoi = b"qoif\0\0\x01\0\0\0\0\x80\x04\0";
+        assert_eq!(
+            describe(qoi),
+            "QOI image data, 256 x 128, RGBA, sRGB with linear alpha"
+        );
+    }
+
+    #[test]
+    fn test_heif_icon() {
+        let mut avif = b"\0\0\0\x18ftypavif\0\0\0\0mif1miaf".to_vec();
+        let ispe = [
+            &b"\0\0\0\x14ispe\0\0\0\0"[..],
+            &1920u32.to_be_bytes(),
+            &1080u32.to_be_bytes(),
+        ]
+        .concat();
+        let pixi = b"\0\0\0\x10pixi\0\0\0\0\x03\x0a\x0a\x0a".to_vec();
+        let ipco = [&b"\0\0\0\x2cipco"[..], &ispe, &pixi].concat();
+        let iprp = [&b"\0\0\0\x34iprp"[..], &ipco].concat();
+        avif.extend([&b"\0\0\0\x40meta\0\0\0\0"[..], &iprp].concat());
+        let classification = identify(&avif).unwrap();
+        assert_eq!(classification.mime_type, "image/avif");
+        assert_eq!(
+            classification.description,
+            "ISO Media, AVIF Image, 1920 x 1080, 10-bit, 3 channels"
+        );
+        assert!(identify(b"\0\0\0\x14ftypisom\0\0\0\0mp41").is_none());
+
+        // a meta box whose 64-bit size overflows its offset
+        let mut large = avif[..0x18].to_vec();
+        large.extend(b"\0\0\0\x01meta");
+        large.extend(u64::MAX.to_be_bytes());
+        assert_eq!(describe(&large), "ISO Media, AVIF Image");
+
+        let mut ico = b"\0\0\x01\0\x01\0\x20\x20\0\0\x01\0\x20\0".to_vec();
+        ico.extend(1024u32.to_le_bytes());
+        ico.extend(22u32.to_le_bytes());
+        assert_eq!(
+            describe(&ico),
+            "MS Windows icon resource - 1 icon, 32x32, 32 bits/pixel"
+        );
+        ico[2] = 2;
+        assert_eq!(
+            describe(&ico),
+            "MS Windows cursor resource - 1 icon, 32x32, hotspot @1x32"
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    fmt::Write,
+    io::{Read, Seek},
+};
+
+use crate::{
+    formats::{Bytes, Seekable, Source},
+    BufferType, Classification, Detector,
+};
+
+const MH_MAGIC: u32 = 0xFEED_FACE;
+const MH_MAGIC_64: u32 = 0xFEED_FACF;
+const FAT_MAGIC: u32 = 0xCAFE_BABE;
+const FAT_MAGIC_64: u32 = 0xCAFE_BABF;
+/// Java class files share the fat magic; their version numbers put them far
+/// above any real architecture count.
+const FAT_ARCHITECTURES_MAX: u32 = 20;
+const CPU_ARCH_ABI64: i32 = 0x0100_0000;
+const CPU_ARCH_ABI64_32: i32 = 0x0200_0000;
+const MH_OBJECT: u32 = 1;
+const MH_DYLIB: u32 = 6;
+const MH_BUNDLE: u32 = 8;
+/// The part of a thin header that is read, which leaves out the reserved
+/// word of 64-bit headers.
+const HEADER_SIZE: u64 = 28;
+
+const FLAGS: [&str; 32] = [
+    "NOUNDEFS",
+    "INCRLINK",
+    "DYLDLINK",
+    "BINDATLOAD",
+    "PREBOUND",
+    "SPLIT_SEGS",
+    "LAZY_INIT",
+    "TWOLEVEL",
+    "FORCE_FLAT",
+    "NOMULTIDEFS",
+    "NOFIXPREBINDING",
+    "PREBINDABLE",
+    "ALLMODSBOUND",
+    "SUBSECTIONS_VIA_SYMBOLS",
+    "CANONICAL",
+    "WEAK_DEFINES",
+    "BINDS_TO_WEAK",
+    "ALLOW_STACK_EXECUTION",
+    "ROOT_SAFE",
+    "SETUID_SAFE",
+    "NO_REEXPORTED_DYLIBS",
+    "PIE",
+    "DEAD_STRIPPABLE_DYLIB",
+    "HAS_TLV_DESCRIPTORS",
+    "NO_HEAP_EXECUTION",
+    "APP_EXTENSION_SAFE",
+    "NLIST_OUTOFSYNC_WITH_DYLDINFO",
+    "SIM_SUPPORT",
+    "0x10000000",
+    "0x20000000",
+    "0x40000000",
+    "DYLIB_IN_CACHE",
+];
+
+/// The header of a single-architecture Mach-O file.
+struct MachO {
+    wide: bool,
+    cpu_type: i32,
+    cpu_subtype: i32,
+    file_type: u32,
+    flags: u32,
+}
+
+/// Parses a thin Mach-O header, or a universal binary along with the header
+/// of every slice that starts inside `data`.
+pub(crate) fn identify(mut data: &[u8]) -> Option<Classification> {
+    classify(&mut data)
+}
+
+/// Like [`identify`], reading the header of every slice of a universal
+/// binary from wherever it lies in `reader`.
+pub(crate) fn identify_seekable(reader: impl Read + Seek) -> Option<Classification> {
+    classify(&mut Seekable::new(reader)?)
+}
+
+fn classify(source: &mut impl Source) -> Option<Classification> {
+    let (description, extension) = match source.read(0, HEADER_SIZE).and_then(|h| parse(&h)) {
+        Some(macho) => (macho.description(), macho.extension()),
+        None => (universal(source)?, None),
+    };
+    let mut classification =
+        Classification::binary(BufferType::MachO, description, "application/x-mach-binary");
+    classification.extensions = extension.map(String::from).into_iter().collect();
+    Some(classification)
+}
+
+/// Runs [`identify`] on files starting with a thin or fat magic number.
+pub(crate) struct MachODetector;
+
+impl Detector for MachODetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        Bytes::new(head, true).u32(0).is_some_and(|magic| {
+            matches!(magic, FAT_MAGIC | FAT_MAGIC_64)
+                || [magic, magic.swap_bytes()]
+                    .iter()
+                    .any(|magic| matches!(*magic, MH_MAGIC | MH_MAGIC_64))
+        })
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+fn parse(data: &[u8]) -> Option<MachO> {
+    let magic = Bytes::new(data, true).u32(0)?;
+    let (wide, big_endian) = match magic {
+        MH_MAGIC => (false, true),
+        MH_MAGIC_64 => (true, true),
+        _ => match magic.swap_bytes() {
+            MH_MAGIC => (false, false),
+            MH_MAGIC_64 => (true, false),
+            _ => return None,
+        },
+    };
+    let bytes = Bytes::new(data, big_endian);
+    Some(MachO {
+        wide,
+        cpu_type: bytes.u32(4)? as i32,
+        cpu_subtype: bytes.u32(8)? as i32,
+        file_type: bytes.u32(12)?,
+        flags: bytes.u32(24)?,
+    })
+}
+
+/// Describes a fat archive as GNU file does, with one bracketed entry per
+/// architecture. Slices whose header cannot be read only show their
+/// architecture.
+fn universal(source: &mut impl Source) -> Option<String> {
+    let fat = source.read(0, 8)?;
+    let bytes = Bytes::new(&fat, true);
+    let wide = match bytes.u32(0)? {
+        FAT_MAGIC => false,
+        FAT_MAGIC_64 => true,
+        _ => return None,
+    };
+    let count = bytes.u32(4)?;
+    if count == 0 || count >= FAT_ARCHITECTURES_MAX {
+        return None;
+    }
+    let mut description = format!(
+        "Mach-O universal binary with {count} architecture{}:",
+        if count == 1 { "" } else { "s" }
+    );
+    let size = if wide { 32 } else { 20 };
+    let architectures = source.read(8, count as u64 * size)?.into_owned();
+    let bytes = Bytes::new(&architectures, true);
+    for index in 0..count as u64 {
+        let at = index * size;
+        let cpu_type = bytes.u32(at)? as i32;
+        let cpu_subtype = bytes.u32(at + 4)? as i32;
+        let offset = if wide {
+            bytes.u64(at + 8)?
+        } else {
+            bytes.u32(at + 8)? as u64
+        };
+        let _ = write!(description, " [{}", architecture(cpu_type, cpu_subtype));
+        if let Some(macho) = source.read(offset, HEADER_SIZE).and_then(|h| parse(&h)) {
+            let _ = write!(description, ":{}", macho.description());
+        }
+        description.push(']');
+    }
+    Some(description)
+}
+
+impl MachO {
+    /// Describes the file the way GNU file does.
+    fn description(&self) -> String {
+        let mut description = String::from("Mach-O ");
+        if self.wide {
+            description.push_str("64-bit ");
+        }
+        description.push_str(&architecture(self.cpu_type, self.cpu_subtype));
+        let file_type = match self.file_type {
+            MH_OBJECT => "object",
+            2 => "executable",
+            3 => "fixed virtual memory shared library",
+            4 => "core",
+            5 => "preload executable",
+            MH_DYLIB => "dynamically linked shared library",
+            7 => "dynamic linker",
+            MH_BUNDLE => "bundle",
+            9 => "shared library stub",
+            10 => "dSYM companion file",
+            11 => "kext bundle",
+            12 => "file set",
+            other => {
+                let _ = write!(description, " filetype={other}");
+                ""
+            }
+        };
+        if !file_type.is_empty() {
+            let _ = write!(description, " {file_type}");
+        }
+        if self.flags != 0 {
+            description.push_str(", flags:<");
+            let names = (0..32)
+                .filter(|bit| self.flags & (1 << bit) != 0)
+                .map(|bit| FLAGS[bit]);
+            for (index, name) in names.enumerate() {
+                if index > 0 {
+                    description.push('|');
+                }
+                description.push_str(name);
+            }
+            description.push('>');
+        }
+        description
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        match self.file_type {
+            MH_OBJECT => Some("o"),
+            MH_DYLIB => Some("dylib"),
+            MH_BUNDLE => Some("bundle"),
+            _ => None,
+        }
+    }
+}
+
+/// Names a CPU type and subtype the way `lipo` and GNU file do.
+fn architecture(cpu_type: i32, cpu_subtype: i32) -> String {
+    let subtype = cpu_subtype & 0x00FF_FFFF;
+    let name = match cpu_type {
+        7 => "i386",
+        0x0100_0007 if subtype == 8 => "x86_64h",
+        0x0100_0007 => "x86_64",
+        12 => match subtype {
+            5 => "armv4t",
+            6 => "armv6",
+            7 => "armv5tej",
+            9 => "armv7",
+            11 => "armv7s",
+            12 => "armv7k",
+            14 => "armv6m",
+            15 => "armv7m",
+            16 => "armv7em",
+            _ => "arm",
+        },
+        0x0100_000C if subtype == 2 => "arm64e",
+        0x0100_000C => "arm64",
+        0x0200_000C => "arm64_32",
+        18 => "ppc",
+        0x0100_0012 => "ppc64",
+        _ => {
+            let width = match cpu_type & !0x00FF_FFFF {
+                CPU_ARCH_ABI64 => "64-bit ",
+                CPU_ARCH_ABI64_32 => "64_32-bit ",
+                _ => "",
+            };
+            return format!("{width}architecture={}", cpu_type & 0x00FF_FFFF);
+        }
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thin(cpu_type: i32, file_type: u32, flags: u32, big_endian: bool) -> Vec<u8> {
+        let words = [MH_MAGIC_64, cpu_type as u32, 0, file_type, 0, 0, flags, 0];
+        words
+            .into_iter()
+            .flat_map(|word| {
+                if big_endian {
+                    word.to_be_bytes()
+                } else {
+                    word.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_identify() {
+        let executable = identify(&thin(0x0100_000C, 2, 0x0020_0085, false)).unwrap();
+        assert_eq!(executable.buffer_type, BufferType::MachO);
+        assert_eq!(
+            executable.description,
+            "Mach-O 64-bit arm64 executable, flags:<NOUNDEFS|DYLDLINK|TWOLEVEL|PIE>"
+        );
+        assert_eq!(executable.mime_type, "application/x-mach-binary");
+
+        let mut ppc = thin(18, MH_BUNDLE, 0, true);
+        ppc[..4].copy_from_slice(&MH_MAGIC.to_be_bytes());
+        let bundle = identify(&ppc).unwrap();
+        assert_eq!(bundle.description, "Mach-O ppc bundle");
+        assert_eq!(bundle.extensions, ["bundle"]);
+
+        let object = identify(&thin(0x0100_0007, MH_OBJECT, 0x2000, false)).unwrap();
+        assert_eq!(
+            object.description,
+            "Mach-O 64-bit x86_64 object, flags:<SUBSECTIONS_VIA_SYMBOLS>"
+        );
+        assert_eq!(object.extensions, ["o"]);
+    }
+
+    #[test]
+    fn test_universal() {
+        let slice = thin(0x0100_0007, MH_DYLIB, 0x4, false);
+        let mut fat = Vec::new();
+        for word in [FAT_MAGIC, 2] {
+            fat.extend(word.to_be_bytes());
+        }
+        let far = 0x18_0000;
+        for (cpu_type, offset) in [(0x0100_0007, 48), (0x0100_000C, far)] {
+            for word in [cpu_type, 0, offset, slice.len() as u32, 12] {
+                fat.extend(word.to_be_bytes());
+            }
+        }
+        fat.extend(&slice);
+        // the second slice starts past the first bytes read
+        fat.resize(far as usize, 0);
+        fat.extend(thin(0x0100_000C, 2, 0x0020_0085, false));
+        let universal = identify_seekable(std::io::Cursor::new(&fat)).unwrap();
+        assert_eq!(
+            universal.description,
+            "Mach-O universal binary with 2 architectures: \
+             [x86_64:Mach-O 64-bit x86_64 dynamically linked shared library, flags:<DYLDLINK>] \
+             [arm64:Mach-O 64-bit arm64 executable, flags:<NOUNDEFS|DYLDLINK|TWOLEVEL|PIE>]"
+        );
+        assert!(universal.extensions.is_empty());
+
+        // a Java class file, version 52
+        assert!(identify(b"\xca\xfe\xba\xbe\x00\x00\x00\x34").is_none());
+    }
+}
@@ -0,0 +1,250 @@
+use std::io::{Read, Seek};
+
+use crate::{formats::Bytes, BufferType, Classification, Detector};
+
+const LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const DATA_DESCRIPTOR: u16 = 0x0008;
+const STORED: u16 = 0;
+/// The longest `mimetype` entry read from an OpenDocument or EPUB file.
+const MIMETYPE_MAX: u64 = 256;
+
+/// What is known about the entries of a zip container.
+#[derive(Default)]
+struct Entries {
+    names: Vec<String>,
+    /// The contents of a stored `mimetype` entry, as ODF and EPUB require.
+    mimetype: Option<String>,
+}
+
+/// Recognises zip-based formats from the local file headers in `data`. Only
+/// entries whose size is known up front can be stepped over, so this is a
+/// best effort; [`identify_seekable`] reads the central directory instead.
+pub(crate) fn identify(data: &[u8]) -> Option<Classification> {
+    let bytes = Bytes::new(data, false);
+    let mut entries = Entries::default();
+    let mut at = 0;
+    while bytes.slice(at, 4) == Some(LOCAL_HEADER) {
+        let (Some(flags), Some(method), Some(size), Some(name_length), Some(extra_length)) = (
+            bytes.u16(at + 6),
+            bytes.u16(at + 8),
+            bytes.u32(at + 18),
+            bytes.u16(at + 26),
+            bytes.u16(at + 28),
+        ) else {
+            break;
+        };
+        let Some(name) = bytes.slice(at + 30, name_length as u64) else {
+            break;
+        };
+        let name = String::from_utf8_lossy(name).into_owned();
+        let contents = at + 30 + name_length as u64 + extra_length as u64;
+        if name == "mimetype" && method == STORED {
+            entries.mimetype = bytes
+                .slice(contents, (size as u64).min(MIMETYPE_MAX))
+                .map(|mimetype| String::from_utf8_lossy(mimetype).into_owned());
+        }
+        entries.names.push(name);
+        if flags & DATA_DESCRIPTOR != 0 && size == 0 {
+            break;
+        }
+        at = contents + size as u64;
+    }
+    entries.classify()
+}
+
+/// Runs [`identify`] on zip archives.
+pub(crate) struct PackageDetector;
+
+impl Detector for PackageDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        head.starts_with(LOCAL_HEADER)
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+/// Recognises zip-based formats from the central directory at the end of
+/// `reader`, which lists every entry however the archive was written.
+pub(crate) fn identify_seekable(reader: impl Read + Seek) -> Option<Classification> {
+    let mut archive = zip::ZipArchive::new(reader).ok()?;
+    let mut entries = Entries {
+        names: archive.file_names().map(str::to_owned).collect(),
+        mimetype: None,
+    };
+    if let Ok(file) = archive.by_name("mimetype") {
+        let mut mimetype = String::new();
+        if file
+            .take(MIMETYPE_MAX)
+            .read_to_string(&mut mimetype)
+            .is_ok()
+        {
+            entries.mimetype = Some(mimetype);
+        }
+    }
+    entries.classify()
+}
+
+impl Entries {
+    fn has(&self, name: &str) -> bool {
+        self.names.iter().any(|entry| entry == name)
+    }
+
+    fn has_prefix(&self, prefix: &str) -> bool {
+        self.names.iter().any(|entry| entry.starts_with(prefix))
+    }
+
+    fn classify(&self) -> Option<Classification> {
+        let (description, mime_type, extension) = if let Some(mimetype) = &self.mimetype {
+            let mimetype = mimetype.trim_end();
+            let (description, extension) = match mimetype {
+                "application/vnd.oasis.opendocument.text" => ("OpenDocument Text", "odt"),
+                "application/vnd.oasis.opendocument.spreadsheet" => {
+                    ("OpenDocument Spreadsheet", "ods")
+                }
+                "application/vnd.oasis.opendocument.presentation" => {
+                    ("OpenDocument Presentation", "odp")
+                }
+                "application/vnd.oasis.opendocument.graphics" => ("OpenDocument Drawing", "odg"),
+                "application/epub+zip" => ("EPUB document", "epub"),
+                _ => return None,
+            };
+            (description, mimetype.to_owned(), extension)
+        } else if self.has("[Content_Types].xml") {
+            let (description, mime_type, extension) = if self.has("extension.vsixmanifest") {
+                (
+                    "Visual Studio extension package (VSIX)",
+                    "application/vsix",
+                    "vsix",
+                )
+            } else if self.has_prefix("word/") {
+                (
+                    "Microsoft Word 2007+",
+                    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+                    "docx",
+                )
+            } else if self.has_prefix("xl/") {
+                (
+                    "Microsoft Excel 2007+",
+                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                    "xlsx",
+                )
+            } else if self.has_prefix("ppt/") {
+                (
+                    "Microsoft PowerPoint 2007+",
+                    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+                    "pptx",
+                )
+            } else {
+                return None;
+            };
+            (description, mime_type.to_owned(), extension)
+        } else if self.has("AndroidManifest.xml") {
+            (
+                "Android package (APK)",
+                String::from("application/vnd.android.package-archive"),
+                "apk",
+            )
+        } else if self
+            .names
+            .iter()
+            .any(|name| name.ends_with(".dist-info/WHEEL") && name.matches('/').count() == 1)
+        {
+            (
+                "Python wheel package",
+                String::from("application/x-wheel+zip"),
+                "whl",
+            )
+        } else if self.has("META-INF/MANIFEST.MF") {
+            (
+                "Java archive data (JAR)",
+                String::from("application/java-archive"),
+                "jar",
+            )
+        } else {
+            return None;
+        };
+        let mut classification =
+            Classification::binary(BufferType::Archive, description.to_owned(), &mime_type);
+        classification.extensions = vec![extension.to_owned()];
+        Some(classification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    fn build(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in entries {
+            writer.start_file(*name, stored).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_identify() {
+        let docx = build(&[
+            ("[Content_Types].xml", b"<Types/>"),
+            ("word/document.xml", b"<document/>"),
+        ]);
+        let classification = identify(&docx).unwrap();
+        assert_eq!(classification.description, "Microsoft Word 2007+");
+        assert_eq!(
+            classification.mime_type,
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(classification.extensions, ["docx"]);
+
+        let odt = build(&[
+            ("mimetype", b"application/vnd.oasis.opendocument.text"),
+            ("content.xml", b"<office/>"),
+        ]);
+        let classification = identify(&odt).unwrap();
+        assert_eq!(classification.description, "OpenDocument Text");
+        assert_eq!(
+            classification.mime_type,
+            "application/vnd.oasis.opendocument.text"
+        );
+
+        let apk = build(&[
+            ("AndroidManifest.xml", b""),
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\n"),
+        ]);
+        assert_eq!(identify(&apk).unwrap().description, "Android package (APK)");
+        let jar = build(&[("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\n")]);
+        assert_eq!(
+            identify(&jar).unwrap().description,
+            "Java archive data (JAR)"
+        );
+        assert!(identify(&build(&[("notes.txt", b"plain")])).is_none());
+    }
+
+    #[test]
+    fn test_identify_seekable() {
+        let wheel = build(&[
+            ("demo/__init__.py", b""),
+            ("demo-1.0.dist-info/METADATA", b"Name: demo\n"),
+            ("demo-1.0.dist-info/WHEEL", b"Wheel-Version: 1.0\n"),
+        ]);
+        // the local headers in the first bytes only show the package itself
+        assert!(identify(&wheel[..60]).is_none());
+        let classification = identify_seekable(Cursor::new(&wheel)).unwrap();
+        assert_eq!(classification.description, "Python wheel package");
+        assert_eq!(classification.mime_type, "application/x-wheel+zip");
+        assert_eq!(classification.extensions, ["whl"]);
+        let epub = build(&[("mimetype", b"application/epub+zip")]);
+        assert_eq!(
+            identify_seekable(Cursor::new(&epub)).unwrap().description,
+            "EPUB document"
+        );
+    }
+}
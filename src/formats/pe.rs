@@ -0,0 +1,218 @@
+use std::fmt::Write;
+
+use crate::{formats::Bytes, BufferType, Classification, Detector};
+
+const PE32: u16 = 0x10B;
+const PE32_PLUS: u16 = 0x20B;
+const IMAGE_FILE_DLL: u16 = 0x2000;
+const CLR_RUNTIME_HEADER: u64 = 14;
+
+/// The parts of a PE image that show up in its description.
+struct Pe {
+    plus: bool,
+    dll: bool,
+    machine: u16,
+    subsystem: u16,
+    /// Whether the image carries a CLR header, making it a .NET assembly.
+    clr: bool,
+    sections: u16,
+}
+
+/// Parses the MZ stub, COFF header and optional header of a Windows
+/// executable or DLL. Plain DOS executables and COFF objects are left to
+/// the magic rules.
+pub(crate) fn identify(data: &[u8]) -> Option<Classification> {
+    let pe = parse(data)?;
+    let mut classification = Classification::binary(
+        BufferType::Pe,
+        pe.description(),
+        "application/vnd.microsoft.portable-executable",
+    );
+    let extension = match pe.subsystem {
+        10..=13 => "efi",
+        _ if pe.dll => "dll",
+        _ => "exe",
+    };
+    classification.extensions = vec![String::from(extension)];
+    Some(classification)
+}
+
+/// Runs [`identify`] on files with an MZ stub.
+pub(crate) struct PeDetector;
+
+impl Detector for PeDetector {
+    fn probe(&self, head: &[u8], _: Option<&[u8]>) -> bool {
+        head.starts_with(b"MZ")
+    }
+
+    fn detect(&self, head: &[u8], _: Option<&[u8]>) -> Option<Classification> {
+        identify(head)
+    }
+}
+
+fn parse(data: &[u8]) -> Option<Pe> {
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+    let bytes = Bytes::new(data, false);
+    let header = bytes.u32(0x3C)? as u64;
+    if bytes.slice(header, 4)? != b"PE\0\0" {
+        return None;
+    }
+    let optional = header + 24;
+    let plus = match bytes.u16(optional)? {
+        PE32 => false,
+        PE32_PLUS => true,
+        _ => return None,
+    };
+    let (count_at, directories_at) = if plus {
+        (optional + 108, optional + 112)
+    } else {
+        (optional + 92, optional + 96)
+    };
+    let optional_size = bytes.u16(header + 20)? as u64;
+    let clr = bytes.u32(count_at)? as u64 > CLR_RUNTIME_HEADER
+        && directories_at + (CLR_RUNTIME_HEADER + 1) * 8 <= optional + optional_size
+        && bytes.u32(directories_at + CLR_RUNTIME_HEADER * 8)? != 0;
+    Some(Pe {
+        plus,
+        dll: bytes.u16(header + 22)? & IMAGE_FILE_DLL != 0,
+        machine: bytes.u16(header + 4)?,
+        subsystem: bytes.u16(optional + 68)?,
+        clr,
+        sections: bytes.u16(header + 6)?,
+    })
+}
+
+impl Pe {
+    /// Describes the image the way GNU file does.
+    fn description(&self) -> String {
+        let mut description = String::from(if self.plus { "PE32+" } else { "PE32" });
+        description.push_str(" executable");
+        if self.dll {
+            description.push_str(" (DLL)");
+        }
+        description.push_str(match self.subsystem {
+            1 => " (native)",
+            2 => " (GUI)",
+            3 => " (console)",
+            7 => " (POSIX CUI)",
+            9 => " (Windows CE GUI)",
+            10 => " (EFI application)",
+            11 => " (EFI boot service driver)",
+            12 => " (EFI runtime driver)",
+            13 => " (EFI ROM)",
+            14 => " (XBOX)",
+            16 => " (Windows boot application)",
+            _ => " (Unknown subsystem)",
+        });
+        description.push(' ');
+        description.push_str(&machine(self.machine));
+        if self.clr {
+            description.push_str(" Mono/.Net assembly");
+        }
+        description.push_str(", for MS Windows");
+        let _ = write!(
+            description,
+            ", {} section{}",
+            self.sections,
+            if self.sections == 1 { "" } else { "s" }
+        );
+        description
+    }
+}
+
+fn machine(machine: u16) -> String {
+    let name = match machine {
+        0x14C => "Intel 80386",
+        0x166 => "MIPS R4000",
+        0x1C0 => "ARM",
+        0x1C2 => "ARM Thumb",
+        0x1C4 => "ARMv7 Thumb",
+        0x1F0 => "PowerPC",
+        0x200 => "Intel Itanium",
+        0x5032 => "RISC-V 32-bit",
+        0x5064 => "RISC-V 64-bit",
+        0x6264 => "LoongArch 64-bit",
+        0x8664 => "x86-64",
+        0xAA64 => "Aarch64",
+        0xEBC => "EFI byte code",
+        _ => return format!("Unknown processor type {machine:#x}"),
+    };
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PE image with a DOS stub pointing at the PE header at 0x80.
+    fn sample(
+        plus: bool,
+        machine: u16,
+        characteristics: u16,
+        subsystem: u16,
+        clr: bool,
+    ) -> Vec<u8> {
+        let mut pe = vec![0; 0x200];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+        pe[0x86..0x88].copy_from_slice(&3u16.to_le_bytes());
+        let optional_size: u16 = if plus { 240 } else { 224 };
+        pe[0x94..0x96].copy_from_slice(&optional_size.to_le_bytes());
+        pe[0x96..0x98].copy_from_slice(&characteristics.to_le_bytes());
+        let optional = 0x98;
+        let magic = if plus { PE32_PLUS } else { PE32 };
+        pe[optional..optional + 2].copy_from_slice(&magic.to_le_bytes());
+        pe[optional + 68..optional + 70].copy_from_slice(&subsystem.to_le_bytes());
+        let (count, directories) = if plus { (108, 112) } else { (92, 96) };
+        pe[optional + count..optional + count + 4].copy_from_slice(&16u32.to_le_bytes());
+        if clr {
+            let at = optional + directories + 14 * 8;
+            pe[at..at + 4].copy_from_slice(&0x2008u32.to_le_bytes());
+        }
+        pe
+    }
+
+    #[test]
+    fn test_identify() {
+        let exe = identify(&sample(true, 0x8664, 0x22, 3, false)).unwrap();
+        assert_eq!(exe.buffer_type, BufferType::Pe);
+        assert_eq!(
+            exe.description,
+            "PE32+ executable (console) x86-64, for MS Windows, 3 sections"
+        );
+        assert_eq!(
+            exe.mime_type,
+            "application/vnd.microsoft.portable-executable"
+        );
+        assert_eq!(exe.extensions, ["exe"]);
+
+        let dll = identify(&sample(false, 0x14C, 0x2102, 2, false)).unwrap();
+        assert_eq!(
+            dll.description,
+            "PE32 executable (DLL) (GUI) Intel 80386, for MS Windows, 3 sections"
+        );
+        assert_eq!(dll.extensions, ["dll"]);
+
+        let assembly = identify(&sample(false, 0x14C, 0x102, 3, true)).unwrap();
+        assert_eq!(
+            assembly.description,
+            "PE32 executable (console) Intel 80386 Mono/.Net assembly, for MS Windows, 3 sections"
+        );
+
+        let efi = identify(&sample(true, 0xAA64, 0x22, 10, false)).unwrap();
+        assert_eq!(
+            efi.description,
+            "PE32+ executable (EFI application) Aarch64, for MS Windows, 3 sections"
+        );
+        assert_eq!(efi.extensions, ["efi"]);
+
+        let mut dos = sample(false, 0x14C, 0x102, 3, false);
+        dos[0x80..0x84].copy_from_slice(b"NE\0\0");
+        assert!(identify(&dos).is_none());
+        assert!(identify(b"MZ").is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fmt::Write, path::Path};
+
+use crate::{BufferState, BufferType};
+
+/// Renders one file's result as a single line JSON object, for `--json` and
+/// `--ndjson`.
+pub(crate) fn object(path: &Path, state: &BufferState) -> String {
+    let mut object = format!("{{\"path\":{}", string(&path.to_string_lossy()));
+    match state {
+        Ok(classification) => {
+            let _ = write!(
+                object,
+                ",\"type\":{},\"description\":{},\"mime_type\":{},\"charset\":{}",
+                string(type_name(classification.buffer_type)),
+                string(&classification.description),
+                string(&classification.mime_type),
+                string(&classification.charset),
+            );
+        }
+        Err(error) => {
+            let _ = write!(
+                object,
+                ",\"error\":{{\"kind\":{},\"message\":{}}}",
+                string(&format!("{:?}", error.kind())),
+                string(&error.to_string()),
+            );
+        }
+    }
+    object.push('}');
+    object
+}
+
+const fn type_name(buffer_type: BufferType) -> &'static str {
+    match buffer_type {
+        BufferType::Empty => "empty",
+        BufferType::Ascii => "ascii",
+        BufferType::Latin1 => "latin1",
+        BufferType::Utf8 => "utf8",
+        BufferType::Utf16 => "utf16",
+        BufferType::Utf32 => "utf32",
+        BufferType::Gb => "gb",
+        BufferType::Text { .. } => "text",
+        BufferType::Data => "data",
+        BufferType::Magic => "magic",
+        BufferType::Directory => "directory",
+        BufferType::Symlink => "symlink",
+        BufferType::Fifo => "fifo",
+        BufferType::Socket => "socket",
+        BufferType::BlockDevice => "block_device",
+        BufferType::CharDevice => "char_device",
+        BufferType::Elf => "elf",
+        BufferType::Pe => "pe",
+        BufferType::MachO => "mach-o",
+        BufferType::Image => "image",
+        BufferType::Compressed => "compressed",
+        BufferType::Archive => "archive",
+        BufferType::Document => "document",
+        BufferType::Audio => "audio",
+        BufferType::Custom => "custom",
+    }
+}
+
+/// Quotes and escapes a JSON string.
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error as IOError, ErrorKind};
+
+    use super::*;
+    use crate::Classification;
+
+    #[test]
+    fn test_object() {
+        let path = Path::new("dir/a: \"b\".txt");
+        assert_eq!(
+            object(path, &Ok(Classification::new(BufferType::Ascii))),
+            "{\"path\":\"dir/a: \\\"b\\\".txt\",\"type\":\"ascii\",\"description\":\"ASCII text\",\
+             \"mime_type\":\"text/plain\",\"charset\":\"us-ascii\"}"
+        );
+        let error = IOError::new(ErrorKind::NotFound, "gone\n");
+        assert_eq!(
+            object(Path::new("x"), &Err(error)),
+            "{\"path\":\"x\",\"error\":{\"kind\":\"NotFound\",\"message\":\"gone\\n\"}}"
+        );
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string("tab\there\u{1}"), "\"tab\\there\\u0001\"");
+    }
+}
@@ -1,94 +1,339 @@
+mod classification;
+mod detector;
+mod formats;
+mod json;
+mod lines;
+mod magic;
+mod members;
+mod special;
 mod vle;
+mod walk;
 
 use std::{
-    cmp::min,
+    borrow::Cow,
     collections::BTreeMap,
     ffi::OsString,
-    fs::File,
-    io::{prelude::*, BufReader, Error as IOError, ErrorKind},
-    path::PathBuf,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader, Cursor, Error as IOError, ErrorKind, SeekFrom},
+    iter,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::mpsc,
     thread,
 };
 
 use itertools::Itertools;
 
-use crate::vle::{
-    gb_sequence::*,
-    unicode::{utf16sequence::*, utf8sequence::*, *},
-    VariableLengthEncoding,
+use crate::walk::Job;
+
+pub use crate::{
+    classification::Classification,
+    detector::{Detector, Detectors},
+    magic::{Magic, MagicMatch},
+    vle::{unicode::Endianness, Encoding, Encodings, VariableLengthEncoding},
 };
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum BufferType {
     Empty,
     Ascii,
     Latin1,
     Utf8,
     Utf16,
+    Utf32,
     Gb,
+    /// Text in an encoding registered through [`Encodings`]. `name` is the
+    /// description and `charset` is reported in MIME output.
+    Text {
+        name: &'static str,
+        charset: &'static str,
+    },
     Data,
+    /// Identified by a magic(5) entry; see [`Classification::description`].
+    Magic,
+    Directory,
+    /// A link that was not followed; the description names its target.
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Elf,
+    /// A Windows PE32 or PE32+ image.
+    Pe,
+    /// A Mach-O file or a universal binary holding several.
+    MachO,
+    /// A raster image; the description gives its dimensions and depth.
+    Image,
+    /// A single compressed stream, such as gzip or xz.
+    Compressed,
+    /// A container of several files, such as zip or tar.
+    Archive,
+    /// A paged document such as PDF.
+    Document,
+    /// An audio file such as MP3.
+    Audio,
+    /// Identified by a [`Detector`] registered outside this crate.
+    Custom,
+}
+
+/// What the printer in [`file_with_options`] reports for each file.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable descriptions such as `ISO 8859-1 text`.
+    #[default]
+    Description,
+    /// `type/subtype; charset=encoding`, like `file -i`.
+    Mime,
+    /// Only the MIME type, like `file --mime-type`.
+    MimeType,
+    /// Only the charset, like `file --mime-encoding`.
+    MimeEncoding,
+    /// A single JSON array with one object per file, in path order.
+    Json,
+    /// One JSON object per line, printed as soon as each file is done.
+    Ndjson,
+}
+
+impl BufferType {
+    pub const fn description(&self) -> &'static str {
+        match self {
+            BufferType::Empty => "empty",
+            BufferType::Ascii => "ASCII text",
+            BufferType::Latin1 => "ISO 8859-1 text",
+            BufferType::Utf8 => "UTF-8 text",
+            BufferType::Utf16 => "UTF-16 text",
+            BufferType::Utf32 => "UTF-32 text",
+            BufferType::Gb => "GB 18030 text",
+            BufferType::Text { name, .. } => name,
+            BufferType::Data => "data",
+            BufferType::Magic => "magic",
+            BufferType::Directory => "directory",
+            BufferType::Symlink => "symbolic link",
+            BufferType::Fifo => "fifo (named pipe)",
+            BufferType::Socket => "socket",
+            BufferType::BlockDevice => "block special",
+            BufferType::CharDevice => "character special",
+            BufferType::Elf => "ELF",
+            BufferType::Pe => "PE",
+            BufferType::MachO => "Mach-O",
+            BufferType::Image => "image",
+            BufferType::Compressed => "compressed data",
+            BufferType::Archive => "archive",
+            BufferType::Document => "document",
+            BufferType::Audio => "audio",
+            BufferType::Custom => "custom",
+        }
+    }
+
+    pub const fn is_text(&self) -> bool {
+        matches!(
+            self,
+            BufferType::Ascii
+                | BufferType::Latin1
+                | BufferType::Utf8
+                | BufferType::Utf16
+                | BufferType::Utf32
+                | BufferType::Gb
+                | BufferType::Text { .. }
+        )
+    }
 }
 
-pub type BufferState = Result<BufferType, IOError>;
+pub type BufferState = Result<Classification, IOError>;
+
+/// Settings for [`file_with_options`].
+#[derive(Default)]
+pub struct Options {
+    /// Rules tried before the text checks; empty by default.
+    pub magic: Magic,
+    /// Parsers tried before the magic rules; the built-in ones by default.
+    pub detectors: Detectors,
+    /// Encodings the text checks try; the built-in ones by default.
+    pub encodings: Encodings,
+    pub format: OutputFormat,
+    /// How many files are classified at once. Defaults to the available
+    /// parallelism.
+    pub jobs: Option<NonZeroUsize>,
+    /// Classify everything inside directories given as arguments.
+    pub recursive: bool,
+    /// How many levels below an argument `recursive` descends, or `None`
+    /// for no limit.
+    pub max_depth: Option<usize>,
+    /// Globs, in `.gitignore` syntax, for paths `recursive` skips.
+    pub exclude: Vec<String>,
+    /// Whether `recursive` skips paths ignored by `.gitignore` files.
+    pub gitignore: bool,
+    /// Classify what symbolic links point to, rather than the links.
+    pub follow_symlinks: bool,
+    /// Read the contents of block and character devices instead of only
+    /// reporting their device numbers.
+    pub read_devices: bool,
+    pub zero_length: ZeroLength,
+    /// Classify the contents of compressed files too, like `file -z`.
+    pub uncompress: bool,
+    /// How many decompressed bytes `uncompress` reads at most. Defaults to
+    /// 64 MiB, so a small file cannot expand without bound.
+    pub uncompress_limit: Option<u64>,
+    /// Classify every member of zip and tar archives as well, reporting
+    /// each as `archive!path/inside`.
+    pub list_archive: bool,
+}
+
+/// Whether a regular file reporting a length of zero is read before it is
+/// called empty.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ZeroLength {
+    /// Read files on kernel pseudo filesystems such as `/proc` and `/sys`,
+    /// whose reported length is meaningless, and trust every other file.
+    #[default]
+    PseudoFiles,
+    Read,
+    Trust,
+}
+
+impl Options {
+    fn worker_count(&self) -> usize {
+        self.jobs
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+
+    /// The `-z` byte limit, or `None` when compressed files stay closed.
+    fn uncompress_limit(&self) -> Option<u64> {
+        self.uncompress
+            .then(|| self.uncompress_limit.unwrap_or(UNCOMPRESS_LIMIT))
+    }
+}
 
 pub fn file(args: impl ExactSizeIterator<Item = OsString>) -> Result<(), IOError> {
+    file_with_options(args, &Options::default())
+}
+
+/// Classifies every path in `args` and prints one result per file. A path of
+/// `-` classifies standard input.
+pub fn file_with_options(
+    args: impl ExactSizeIterator<Item = OsString>,
+    options: &Options,
+) -> Result<(), IOError> {
     if args.len() == 0 {
         return Err(IOError::new(
             ErrorKind::InvalidInput,
             "Invalid number of arguments",
         ));
     }
+    if options.recursive {
+        // reject bad globs before anything is printed
+        walk::exclusions(Path::new(""), &options.exclude)?;
+    }
+    // gets rid of duplicate file paths so we don't do work twice
+    let paths: Vec<PathBuf> = args
+        .unique_by(|a| std::fs::canonicalize(a).unwrap_or(PathBuf::from(a)))
+        .map(PathBuf::from)
+        .collect();
+    let workers = if options.recursive {
+        options.worker_count()
+    } else {
+        options.worker_count().min(paths.len())
+    };
+    let (sender, receiver) = mpsc::sync_channel(workers * 64);
+    let receiver = parking_lot::const_mutex(receiver);
     let shared_file_states = parking_lot::const_mutex(BTreeMap::new());
     thread::scope(|s| {
-        for arg in args.unique_by(|a| std::fs::canonicalize(a).unwrap_or(PathBuf::from(a))) {
-            // gets rid of duplicate file paths so we don't do work twice
+        for _ in 0..workers {
             s.spawn(|| {
-                let path = PathBuf::from(arg);
-                let metadata = std::fs::metadata(&path);
-                if let Err(error) = metadata {
-                    let mut file_states = shared_file_states.lock();
-                    file_states.insert(path, Err(error));
-                    return;
-                }
-                let bytes = metadata.unwrap().len();
-                if bytes == 0 {
+                while let Ok(job) = receiver.lock().recv() {
+                    let (path, data) = match job {
+                        Job::Classify(path) => {
+                            let data = classify_path(&path, options);
+                            (path, data)
+                        }
+                        Job::Stdin => {
+                            let data = classify_stream(io::stdin().lock(), options);
+                            (PathBuf::from("/dev/stdin"), data)
+                        }
+                        Job::Failed(path, error) => (path, Err(error)),
+                    };
+                    let members = match &data {
+                        Ok(classification) if options.list_archive => {
+                            members::members(&path, classification, options)
+                        }
+                        _ => Vec::new(),
+                    };
+                    let results = iter::once((path, data)).chain(members);
+                    if options.format == OutputFormat::Ndjson {
+                        // streamed as soon as each file is done
+                        for (path, data) in results {
+                            println!("{}", json::object(&path, &data));
+                        }
+                        continue;
+                    }
                     let mut file_states = shared_file_states.lock();
-                    file_states.insert(path, Ok(BufferType::Empty));
-                    return;
+                    file_states.extend(results);
                 }
-                let file = match File::open(&path) {
-                    Ok(open_file) => open_file,
-                    Err(error) => {
-                        let mut file_states = shared_file_states.lock();
-                        file_states.insert(path, Err(error));
-                        return;
-                    }
-                };
-                let reader = BufReader::with_capacity(min(8 * 1024, bytes as usize), file);
-                let data = classify_file(reader);
-                let mut file_states = shared_file_states.lock();
-                file_states.insert(path, data);
             });
         }
+        walk::send_paths(&paths, options, &sender);
+        drop(sender);
     });
     let file_states = shared_file_states.into_inner();
+    if options.format == OutputFormat::Json {
+        let objects = file_states
+            .iter()
+            .map(|(path, file_result)| json::object(path, file_result))
+            .join(",\n");
+        println!("[{objects}]");
+        return Ok(());
+    }
     for (path, file_result) in file_states {
-        let message = match file_result {
-            Ok(file_type) => match file_type {
-                BufferType::Empty => "empty",
-                BufferType::Ascii => "ASCII text",
-                BufferType::Latin1 => "ISO 8859-1 text",
-                BufferType::Utf8 => "UTF-8 text",
-                BufferType::Utf16 => "UTF-16 text",
-                BufferType::Gb => "GB 18030 text",
-                BufferType::Data => "data",
-            },
-            Err(error) => &error.to_string(),
+        let message = match &file_result {
+            Ok(file_type) => file_type.format(options.format),
+            Err(error) => Cow::Owned(error.to_string()),
         };
         println!("{}: {message}", path.display());
     }
     Ok(())
 }
 
+fn classify_path(path: &Path, options: &Options) -> BufferState {
+    let metadata = if options.follow_symlinks {
+        match fs::metadata(path) {
+            Err(error) if path.is_symlink() && error.kind() == ErrorKind::NotFound => {
+                return special::symlink(path);
+            }
+            metadata => metadata?,
+        }
+    } else {
+        fs::symlink_metadata(path)?
+    };
+    if let Some(classification) = special::classify(path, &metadata, options.read_devices)? {
+        return Ok(classification);
+    }
+    let sized = metadata.is_file() && metadata.len() != 0;
+    if metadata.is_file() && !sized {
+        let read = match options.zero_length {
+            ZeroLength::PseudoFiles => special::is_pseudo_file(path),
+            ZeroLength::Read => true,
+            ZeroLength::Trust => false,
+        };
+        if !read {
+            return Ok(Classification::new(BufferType::Empty));
+        }
+    }
+    let file = File::open(path)?;
+    if sized {
+        return classify_seekable_with_options(file, options);
+    }
+    classify_stream(file, options)
+}
+
+/// Classifies a pipe, device or other stream without a trustworthy length.
+/// Those may never end, so only as much is read as magic would look at.
+fn classify_stream(reader: impl Read, options: &Options) -> BufferState {
+    let reader = BufReader::with_capacity(8 * 1024, reader.take(MAGIC_BYTES_MAX as u64));
+    classify_file_with_options(reader, options)
+}
+
 const fn is_byte_ascii(byte: u8) -> bool {
     matches!(byte, 0x08..=0x0D | 0x1B | 0x20..=0x7E)
 }
@@ -97,104 +342,247 @@ const fn is_byte_latin1(byte: u8) -> bool {
     is_byte_ascii(byte) || byte >= 0xA0
 }
 
+/// How much of a file magic rules can see, matching libmagic's historical
+/// default read size.
+const MAGIC_BYTES_MAX: usize = 1024 * 1024;
+const UNCOMPRESS_LIMIT: u64 = 64 * 1024 * 1024;
+/// How much of the end of a seekable file the format parsers see.
+const TAIL_BYTES: u64 = 4096;
+
 pub fn classify_file(reader: impl Read) -> BufferState {
-    let [mut is_ascii, mut is_utf8, mut is_utf16, mut is_latin1, mut is_gb] = [true; 5];
-    let mut utf8_sequence: Option<Utf8Sequence> = None;
-    let mut utf16_sequence: Option<Utf16Sequence> = None;
-    let mut gb_sequence: Option<GbSequence> = None;
-    let mut endianness: Option<Endianness> = None;
-    let mut byte_buffer = [0; 2];
-    let mut bytes_read = 0;
-    for result_byte in reader.bytes() {
-        let byte = result_byte?;
-        bytes_read += 1;
-        if is_ascii && !is_byte_ascii(byte) {
-            is_ascii = false;
-        }
-        if !is_ascii && is_utf16 {
-            byte_buffer[(bytes_read - 1) % 2] = byte;
-            if bytes_read % 2 == 0 {
-                if let Some(endian_value) = endianness {
-                    let point = match endian_value {
-                        Endianness::BigEndian => {
-                            u16::from_be_bytes([byte_buffer[0], byte_buffer[1]])
-                        }
-                        Endianness::LittleEndian => {
-                            u16::from_le_bytes([byte_buffer[0], byte_buffer[1]])
-                        }
-                    };
-                    validate_vle(&mut is_utf16, &mut utf16_sequence, point);
-                } else {
-                    let be = u16::from_be_bytes(byte_buffer);
-                    let le = u16::from_le_bytes(byte_buffer);
-                    if be == 0xFEFF {
-                        endianness = Some(Endianness::BigEndian);
-                    } else if le == 0xFEFF {
-                        endianness = Some(Endianness::LittleEndian);
-                    } else {
-                        is_utf16 = false;
-                    }
-                }
-            }
+    classify_file_with_magic(reader, &Magic::new())
+}
+
+/// Classifies a file by running the native format parsers and then `magic`
+/// over its first bytes, before falling back to the text encoding checks.
+/// Text-only magic entries are tried once the encoding is known, and their
+/// description is put in front of it.
+pub fn classify_file_with_magic(reader: impl Read, magic: &Magic) -> BufferState {
+    let classifier = Classifier {
+        magic,
+        detectors: Detectors::builtin(),
+        encodings: Encodings::builtin(),
+        uncompress_limit: None,
+    };
+    classifier.classify(reader, None)
+}
+
+/// Like [`classify_file_with_magic`] with the magic, detectors and encodings
+/// of `options`, also looking inside compressed files when
+/// `options.uncompress` is set.
+pub fn classify_file_with_options(reader: impl Read, options: &Options) -> BufferState {
+    Classifier::new(options).classify(reader, None)
+}
+
+/// Like [`classify_file`], for readers that can seek. Formats that keep
+/// their signature or directory at the end, such as zip packages, PDF
+/// trailers and disk images, are only recognised from a seekable reader
+/// unless the whole file fits in the first bytes read.
+pub fn classify_seekable(reader: impl Read + Seek) -> BufferState {
+    classify_seekable_with_options(reader, &Options::default())
+}
+
+/// Like [`classify_seekable`], with the settings of `options` that
+/// [`classify_file_with_options`] uses. Classification starts at the
+/// current position of `reader`.
+pub fn classify_seekable_with_options(
+    mut reader: impl Read + Seek,
+    options: &Options,
+) -> BufferState {
+    let start = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(end.saturating_sub(TAIL_BYTES).max(start)))?;
+    let mut tail = Vec::new();
+    reader.by_ref().take(TAIL_BYTES).read_to_end(&mut tail)?;
+    reader.seek(SeekFrom::Start(start))?;
+    let head = BufReader::with_capacity(8 * 1024, Read::by_ref(&mut reader));
+    let classification = Classifier::new(options).classify(head, Some(&tail))?;
+    let beyond_head = end.saturating_sub(start) > MAGIC_BYTES_MAX as u64;
+    reader.seek(SeekFrom::Start(start))?;
+    let refined = match classification.buffer_type {
+        // zip-based formats may only show what they are in the central
+        // directory, when their first entries are not telling
+        BufferType::Archive if classification.mime_type == "application/zip" => {
+            formats::package::identify_seekable(BufReader::with_capacity(8 * 1024, reader))
         }
-        if !is_ascii && is_utf8 {
-            validate_vle(&mut is_utf8, &mut utf8_sequence, byte);
+        // linkers put the tables and slices executables are described by
+        // anywhere
+        BufferType::Elf if beyond_head => formats::elf::identify_seekable(reader),
+        BufferType::MachO if beyond_head => formats::macho::identify_seekable(reader),
+        _ => None,
+    };
+    Ok(refined.unwrap_or(classification))
+}
+
+/// The settings the classification entry points share.
+struct Classifier<'a> {
+    magic: &'a Magic,
+    detectors: &'a Detectors,
+    encodings: &'a Encodings,
+    /// The `-z` byte limit, or `None` when compressed files stay closed.
+    uncompress_limit: Option<u64>,
+}
+
+impl<'a> Classifier<'a> {
+    fn new(options: &'a Options) -> Self {
+        Self {
+            magic: &options.magic,
+            detectors: &options.detectors,
+            encodings: &options.encodings,
+            uncompress_limit: options.uncompress_limit(),
         }
-        if !is_ascii && is_gb {
-            validate_vle(&mut is_gb, &mut gb_sequence, byte);
+    }
+
+    /// Classifies `reader`, given the last bytes of the file in `tail` when
+    /// it could be seeked to them.
+    fn classify(&self, mut reader: impl Read, tail: Option<&[u8]>) -> BufferState {
+        let mut head = Vec::new();
+        reader
+            .by_ref()
+            .take(MAGIC_BYTES_MAX as u64)
+            .read_to_end(&mut head)?;
+        let complete = head.len() < MAGIC_BYTES_MAX;
+        let tail = if complete {
+            Some(head.as_slice())
+        } else {
+            tail
+        };
+        if let Some(classification) = self.detectors.identify(&head, tail) {
+            return Ok(match self.uncompress_limit {
+                Some(limit) if classification.buffer_type == BufferType::Compressed => {
+                    self.uncompressed(classification, &head, reader, limit)
+                }
+                _ => classification,
+            });
         }
-        if !is_ascii && is_latin1 && !is_byte_latin1(byte) {
-            is_latin1 = false;
+        let encoding = classify_text(Cursor::new(&head), self.encodings)?;
+        let looks_text = encoding.buffer_type.is_text();
+        if let Some(found) = self.magic.identify_binary(&head, complete, looks_text) {
+            return Ok(Classification::magic(found, encoding, false));
         }
-        if !is_ascii && !is_utf16 && !is_utf8 && !is_gb && !is_latin1 {
-            return Ok(BufferType::Data);
+        let mut classification = classify_text(Cursor::new(&head).chain(reader), self.encodings)?;
+        if !classification.buffer_type.is_text() {
+            return Ok(classification);
         }
+        lines::qualify(&mut classification, &head);
+        Ok(match self.magic.identify_text(&head, complete) {
+            Some(found) => Classification::magic(found, classification, true),
+            None => classification,
+        })
     }
-    if utf16_sequence.is_some() {
-        is_utf16 = false;
-    }
-    if utf8_sequence.is_some() {
-        is_utf8 = false;
-    }
-    if gb_sequence.is_some() {
-        is_gb = false;
+
+    /// Adds what the decompressed contents are to the description of a
+    /// compressed file. Streams that fail to decompress keep the outer
+    /// result.
+    fn uncompressed(
+        &self,
+        mut classification: Classification,
+        head: &[u8],
+        reader: impl Read,
+        limit: u64,
+    ) -> Classification {
+        let Some(decoder) = formats::compressed::decoder(head, Cursor::new(head).chain(reader))
+        else {
+            return classification;
+        };
+        let inner = Self {
+            uncompress_limit: None,
+            ..*self
+        };
+        if let Ok(inner) = inner.classify(decoder.take(limit), None) {
+            classification.description =
+                format!("{} ({})", classification.description, inner.description);
+        }
+        classification
     }
-    return match [is_ascii, is_utf16, is_utf8, is_latin1, is_gb] {
-        [true, _, _, _, _] => Ok(BufferType::Ascii),
-        [_, true, _, _, _] => Ok(BufferType::Utf16),
-        [_, _, true, _, _] => Ok(BufferType::Utf8),
-        [_, _, _, true, _] => Ok(BufferType::Latin1),
-        [_, _, _, _, true] => Ok(BufferType::Gb),
-        [_, _, _, _, _] => Ok(BufferType::Data),
-    };
+}
 
-    #[inline]
-    fn validate_vle<T: VariableLengthEncoding>(
-        is_valid: &mut bool,
-        vle_sequence: &mut Option<T>,
-        point: T::Point,
-    ) {
-        if let Some(sequence) = vle_sequence.as_mut() {
-            if !sequence.is_complete() && !sequence.add_point(point) {
-                *is_valid = false;
-                return;
-            }
-            if sequence.is_complete() {
-                if !sequence.is_valid() {
-                    *is_valid = false;
-                }
-                *vle_sequence = None;
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// Finds every encoding in `encodings` the text is valid in. Validators
+/// only start at the chunk holding the first byte outside ASCII, so text
+/// that is ASCII throughout costs one pass over its bytes.
+fn classify_text(mut reader: impl Read, encodings: &Encodings) -> BufferState {
+    let mut validators: Vec<(&Encoding, Box<dyn vle::Validator>, bool)> = Vec::new();
+    let mut is_ascii = true;
+    let mut prefix = [0; UTF8_BOM.len()];
+    let mut bytes_read = 0;
+    let mut chunk = [0; 8 * 1024];
+    loop {
+        let length = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        let chunk = &chunk[..length];
+        if let Some(missing) = prefix.get_mut(bytes_read..) {
+            let copied = missing.len().min(length);
+            missing[..copied].copy_from_slice(&chunk[..copied]);
+        }
+        if is_ascii {
+            if chunk.iter().all(|&byte| is_byte_ascii(byte)) {
+                bytes_read += length;
+                continue;
             }
-        } else if let Some(sequence) = T::build(point) {
-            if !sequence.is_complete() {
-                *vle_sequence = Some(sequence);
-            } else if !sequence.is_valid() {
-                *is_valid = false;
+            // from the start of the chunk, so that UTF-16 without a byte
+            // order mark is read in whole code units
+            is_ascii = false;
+            validators = start_validators(encodings, bytes_read);
+        }
+        bytes_read += length;
+        let mut any_valid = false;
+        for (_, validator, is_valid) in &mut validators {
+            if *is_valid {
+                *is_valid = validator.push(chunk);
+                any_valid |= *is_valid;
             }
-        } else {
-            *is_valid = false;
+        }
+        if !any_valid {
+            return Ok(Classification::new(BufferType::Data));
         }
     }
+    if bytes_read == 0 {
+        return Ok(Classification::new(BufferType::Empty));
+    }
+    if is_ascii {
+        validators = start_validators(encodings, bytes_read);
+    }
+    let valid: Vec<_> = validators
+        .iter()
+        .filter(|(_, validator, is_valid)| *is_valid && validator.finish())
+        .collect();
+    let candidates: Vec<_> = is_ascii
+        .then_some(BufferType::Ascii)
+        .into_iter()
+        .chain(valid.iter().map(|(encoding, ..)| encoding.buffer_type()))
+        .collect();
+    let buffer_type = candidates.first().copied().unwrap_or(BufferType::Data);
+    let winner = valid
+        .iter()
+        .find(|(encoding, ..)| encoding.buffer_type() == buffer_type);
+    let bom = match buffer_type {
+        BufferType::Utf8 => prefix == UTF8_BOM,
+        _ => winner.is_some_and(|(_, validator, _)| validator.bom()),
+    };
+    let endianness = winner.and_then(|(_, validator, _)| validator.endianness());
+    Ok(Classification::text(
+        buffer_type,
+        endianness,
+        bom,
+        candidates,
+    ))
+}
+
+/// A validator for each encoding, for text pushed from offset `start`.
+fn start_validators(
+    encodings: &Encodings,
+    start: usize,
+) -> Vec<(&Encoding, Box<dyn vle::Validator>, bool)> {
+    encodings
+        .iter()
+        .map(|encoding| (encoding, encoding.validator(start), true))
+        .collect()
 }
 
 #[cfg(test)]
@@ -207,9 +595,13 @@ mod tests {
             include_bytes!("../test_files/harpers_ASCII.txt"),
         ];
         let result = ascii.map(|bytes| classify_file(BufReader::new(bytes)));
-        assert!(result
-            .iter()
-            .all(|state| matches!(state, Ok(BufferType::Ascii))));
+        assert!(result.iter().all(|state| matches!(
+            state,
+            Ok(Classification {
+                buffer_type: BufferType::Ascii,
+                ..
+            })
+        )));
     }
     #[test]
     fn test_latin1() {
@@ -219,9 +611,13 @@ mod tests {
             include_bytes!("../test_files/portugal_ISO-8859-1.txt"),
         ];
         let result = latin1.map(|bytes| classify_file(BufReader::new(bytes)));
-        assert!(result
-            .iter()
-            .all(|state| matches!(state, Ok(BufferType::Latin1))))
+        assert!(result.iter().all(|state| matches!(
+            state,
+            Ok(Classification {
+                buffer_type: BufferType::Latin1,
+                ..
+            })
+        )))
     }
     #[test]
     fn test_utf8() {
@@ -231,9 +627,13 @@ mod tests {
             include_bytes!("../test_files/shisei_UTF-8.txt"),
         ];
         let result = utf8.map(|bytes| classify_file(BufReader::new(bytes)));
-        assert!(result
-            .iter()
-            .all(|state| matches!(state, Ok(BufferType::Utf8))));
+        assert!(result.iter().all(|state| matches!(
+            state,
+            Ok(Classification {
+                buffer_type: BufferType::Utf8,
+                ..
+            })
+        )));
     }
     #[test]
     fn test_utf16() {
@@ -244,16 +644,72 @@ mod tests {
             include_bytes!("../test_files/shisei_UTF-16BE.txt"),
         ];
         let result = utf16.map(|bytes| classify_file(BufReader::new(bytes)));
-        assert!(result
-            .iter()
-            .all(|state| matches!(state, Ok(BufferType::Utf16))));
+        assert!(result.iter().all(|state| matches!(
+            state,
+            Ok(Classification {
+                buffer_type: BufferType::Utf16,
+                bom: true,
+                ..
+            })
+        )));
+        assert_eq!(
+            result[0].as_ref().unwrap().endianness,
+            Some(Endianness::LittleEndian)
+        );
+        assert_eq!(result[1].as_ref().unwrap().charset, "utf-16be");
+    }
+    #[test]
+    fn test_utf16_without_bom() {
+        let latin1: &[u8] = include_bytes!("../test_files/die_ISO-8859-1.txt");
+        let little: Vec<u8> = latin1.iter().flat_map(|&byte| [byte, 0]).collect();
+        let big: Vec<u8> = latin1.iter().flat_map(|&byte| [0, byte]).collect();
+        let [little, big] = [little, big].map(|bytes| classify_file(bytes.as_slice()).unwrap());
+        assert_eq!(little.buffer_type, BufferType::Utf16);
+        assert!(!little.bom);
+        assert_eq!(
+            little.description,
+            "UTF-16 text (no BOM), with CRLF line terminators"
+        );
+        assert_eq!(little.charset, "utf-16le");
+        assert_eq!(big.endianness, Some(Endianness::BigEndian));
+        assert_eq!(big.charset, "utf-16be");
+        // zero bytes at both offsets are not text in either byte order
+        let mixed = b"a\0\0b\0c\0\0".as_slice();
+        assert_eq!(classify_file(mixed).unwrap().buffer_type, BufferType::Data);
+        let single = b"a\0".as_slice();
+        assert_eq!(classify_file(single).unwrap().buffer_type, BufferType::Data);
+    }
+    #[test]
+    fn test_utf32() {
+        let utf32: [&[u8]; 2] = [
+            include_bytes!("../test_files/le_utf32.txt"),
+            include_bytes!("../test_files/be_utf32.txt"),
+        ];
+        let [le, be] = utf32.map(|bytes| classify_file(BufReader::new(bytes)).unwrap());
+        // the UTF-32LE mark starts with the UTF-16LE one
+        assert_eq!(le.buffer_type, BufferType::Utf32);
+        assert!(le.bom);
+        assert_eq!(le.endianness, Some(Endianness::LittleEndian));
+        assert_eq!(le.charset, "utf-32le");
+        assert_eq!(be.description, "UTF-32 text, with CRLF line terminators");
+        assert_eq!(be.charset, "utf-32be");
+        let surrogate = b"\xff\xfe\0\0a\0\0\0\x00\xd8\0\0".as_slice();
+        assert_eq!(
+            classify_file(surrogate).unwrap().buffer_type,
+            BufferType::Data
+        );
+        let beyond = b"\0\0\xfe\xff\0\x11\0\0".as_slice();
+        assert_eq!(classify_file(beyond).unwrap().buffer_type, BufferType::Data);
     }
     #[test]
     fn test_data() {
         let data: &[u8] = include_bytes!("../test_files/data.data");
         assert!(matches!(
             classify_file(BufReader::new(data)),
-            Ok(BufferType::Data)
+            Ok(Classification {
+                buffer_type: BufferType::Data,
+                ..
+            })
         ));
     }
     #[test]
@@ -263,8 +719,249 @@ mod tests {
             include_bytes!("../test_files/gb.txt"),
         ];
         let result = data.map(|bytes| classify_file(BufReader::new(bytes)));
-        assert!(result
-            .iter()
-            .all(|state| matches!(state, Ok(BufferType::Gb))));
+        assert!(result.iter().all(|state| matches!(
+            state,
+            Ok(Classification {
+                buffer_type: BufferType::Gb,
+                ..
+            })
+        )));
+    }
+    #[test]
+    fn test_magic() {
+        let magic = Magic::parse(
+            "0\tstring\t\\x89PNG\tPNG image data\n\
+             >16\tbelong\tx\t\\b, %d x\n\
+             >20\tbelong\tx\t%d\n\
+             0\tsearch/1\t#!/bin/sh\tPOSIX shell script\n",
+        )
+        .unwrap();
+        let png: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0\0\0\0\x20";
+        assert!(matches!(
+            classify_file_with_magic(png, &magic),
+            Ok(found) if found.description == "PNG image data, 256 x 32"
+        ));
+        let script: &[u8] = b"#!/bin/sh\necho hi\n";
+        assert!(matches!(
+            classify_file_with_magic(script, &magic),
+            Ok(found) if found.description == "POSIX shell script, ASCII text"
+        ));
+        let ascii: &[u8] = include_bytes!("../test_files/ascii.txt");
+        assert!(matches!(
+            classify_file_with_magic(ascii, &magic),
+            Ok(Classification {
+                buffer_type: BufferType::Ascii,
+                ..
+            })
+        ));
+    }
+    #[test]
+    fn test_classification() {
+        let utf8: &[u8] = include_bytes!("../test_files/shisei_UTF-8.txt");
+        let utf8 = classify_file(utf8).unwrap();
+        assert!(utf8.bom);
+        assert_eq!(
+            utf8.description,
+            "UTF-8 text, with BOM, with very long lines (1753), with CRLF line terminators"
+        );
+        assert_eq!(utf8.endianness, None);
+        assert_eq!(utf8.candidates, [BufferType::Utf8]);
+        let ascii: &[u8] = include_bytes!("../test_files/ascii.txt");
+        let ascii = classify_file(ascii).unwrap();
+        assert!(!ascii.bom);
+        assert_eq!(
+            ascii.candidates,
+            [
+                BufferType::Ascii,
+                BufferType::Utf8,
+                BufferType::Latin1,
+                BufferType::Gb
+            ]
+        );
+        let magic = Magic::parse("0\tstring\tGIF8\tGIF image data\n!:ext\tgif\n").unwrap();
+        let gif = classify_file_with_magic(b"GIF89a".as_slice(), &magic).unwrap();
+        assert_eq!(gif.buffer_type, BufferType::Magic);
+        assert_eq!(gif.extensions, ["gif"]);
+        assert!(gif.candidates.is_empty());
+    }
+    #[test]
+    fn test_mime() {
+        let latin1: &[u8] = include_bytes!("../test_files/iso8859-1.txt");
+        let latin1 = classify_file(latin1).unwrap();
+        assert_eq!(latin1.mime(), "text/plain; charset=iso-8859-1");
+        assert_eq!(latin1.format(OutputFormat::MimeType), "text/plain");
+        let gb: &[u8] = include_bytes!("../test_files/gb.txt");
+        let gb = classify_file(gb).unwrap();
+        assert_eq!(gb.format(OutputFormat::MimeEncoding), "gb18030");
+        assert_eq!(
+            Classification::new(BufferType::Empty).mime(),
+            "inode/x-empty; charset=binary"
+        );
+        assert_eq!(
+            Classification::new(BufferType::Data).mime(),
+            "application/octet-stream; charset=binary"
+        );
+        let magic = Magic::parse(
+            "0\tstring\t\\x89PNG\tPNG image data\n!:mime\timage/png\n\
+             0\tsearch/1\t#!/bin/sh\tPOSIX shell script\n!:mime\ttext/x-shellscript\n",
+        )
+        .unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let png = classify_file_with_magic(png.as_slice(), &magic).unwrap();
+        assert_eq!(png.mime(), "image/png; charset=binary");
+        let script = classify_file_with_magic(b"#!/bin/sh\n".as_slice(), &magic).unwrap();
+        assert_eq!(script.mime(), "text/x-shellscript; charset=us-ascii");
+        // binary entries without a MIME type keep the encoding found
+        let magic = Magic::parse("0\tstring\t\\xff\\xfe\\0\\0\tUnicode text, UTF-32\n").unwrap();
+        let utf32 = b"\xff\xfe\0\0h\0\0\0i\0\0\0\n\0\0\0";
+        let utf32 = classify_file_with_magic(utf32.as_slice(), &magic).unwrap();
+        assert_eq!(utf32.description, "Unicode text, UTF-32");
+        assert_eq!(utf32.mime(), "text/plain; charset=utf-32le");
+        let magic = Magic::parse("0\tstring\tHello\tgreeting\n!:mime\ttext/x-greeting\n").unwrap();
+        let greeting = classify_file_with_magic("Hello, café".as_bytes(), &magic).unwrap();
+        assert_eq!(greeting.mime(), "text/x-greeting; charset=utf-8");
+    }
+
+    #[test]
+    fn test_uncompress() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(b"hello\n").unwrap();
+        encoder.write_all(&[0; 4096]).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let closed = classify_file(gzip.as_slice()).unwrap();
+        assert_eq!(closed.description, "gzip compressed data, max compression");
+        let mut options = Options {
+            uncompress: true,
+            ..Options::default()
+        };
+        let opened = classify_file_with_options(gzip.as_slice(), &options).unwrap();
+        assert_eq!(opened.buffer_type, BufferType::Compressed);
+        assert_eq!(
+            opened.description,
+            "gzip compressed data, max compression (data)"
+        );
+        options.uncompress_limit = Some(6);
+        let limited = classify_file_with_options(gzip.as_slice(), &options).unwrap();
+        assert_eq!(
+            limited.description,
+            "gzip compressed data, max compression (ASCII text)"
+        );
+    }
+    #[test]
+    fn test_encodings() {
+        struct Big5Sequence([u8; 2], bool);
+        impl VariableLengthEncoding for Big5Sequence {
+            type Point = u8;
+            fn build(byte: u8) -> Option<Self> {
+                match byte {
+                    0x20..=0x7E | b'\n' => Some(Self([byte, 0], true)),
+                    0x81..=0xFE => Some(Self([byte, 0], false)),
+                    _ => None,
+                }
+            }
+            fn is_complete(&self) -> bool {
+                self.1
+            }
+            fn add_point(&mut self, byte: u8) -> bool {
+                self.0[1] = byte;
+                self.1 = true;
+                matches!(byte, 0x40..=0x7E | 0xA1..=0xFE)
+            }
+            fn is_valid(&self) -> bool {
+                self.1
+            }
+        }
+        let big5 = b"\xa4\xa4\xa4\xe5\n".as_slice();
+        assert_eq!(classify_file(big5).unwrap().buffer_type, BufferType::Latin1);
+        let mut options = Options::default();
+        options
+            .encodings
+            .register(Encoding::new::<Big5Sequence>("Big5 text", "big5", 15));
+        let classification = classify_file_with_options(big5, &options).unwrap();
+        assert_eq!(classification.description, "Big5 text");
+        assert_eq!(classification.mime(), "text/plain; charset=big5");
+        assert_eq!(
+            classification.candidates[1..],
+            [BufferType::Latin1, BufferType::Gb]
+        );
+        options.encodings = Encodings::empty();
+        let classification = classify_file_with_options(big5, &options).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Data);
+        let ascii = classify_file_with_options(b"text\n".as_slice(), &options).unwrap();
+        assert_eq!(ascii.candidates, [BufferType::Ascii]);
+    }
+    #[test]
+    fn test_seekable() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("demo/model.bin", stored).unwrap();
+        writer.write_all(&vec![0; MAGIC_BYTES_MAX * 2]).unwrap();
+        writer
+            .start_file("demo-1.0.dist-info/WHEEL", stored)
+            .unwrap();
+        writer.write_all(b"Wheel-Version: 1.0\n").unwrap();
+        let wheel = writer.finish().unwrap().into_inner();
+        // the dist-info entries are past the first bytes, so only the
+        // central directory shows what this is
+        let streamed = classify_file(wheel.as_slice()).unwrap();
+        assert_eq!(streamed.mime_type, "application/zip");
+        let seeked = classify_seekable(Cursor::new(&wheel)).unwrap();
+        assert_eq!(seeked.description, "Python wheel package");
+
+        let mut dmg = vec![0; MAGIC_BYTES_MAX * 2];
+        let trailer = dmg.len() - 512;
+        dmg[trailer..trailer + 8].copy_from_slice(b"koly\0\0\0\x04");
+        assert_eq!(classify_file(dmg.as_slice()).unwrap().description, "data");
+        let seeked = classify_seekable(Cursor::new(&dmg)).unwrap();
+        assert_eq!(seeked.description, "Apple disk image (UDIF)");
+        assert_eq!(seeked.mime_type, "application/x-apple-diskimage");
+    }
+    #[test]
+    fn test_compiled_magic() {
+        let sources = std::env::temp_dir().join(format!("file-rs-magic-{}", std::process::id()));
+        std::fs::create_dir_all(&sources).unwrap();
+        std::fs::write(
+            sources.join("marks"),
+            "0\tbeshort\t0xfeff\tbig-endian byte order mark\n\
+             0\tleshort\t0xfeff\tlittle-endian byte order mark\n\
+             0\tstring\t\\xef\\xbb\\xbf\tUTF-8 byte order mark\n\
+             >3\tuse\tword\n\
+             0\tname\tword\n\
+             >0\tstring\tx\t\\b, starting \"%.7s\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            sources.join("texts"),
+            "0\tsearch/4096\tProject\\ Gutenberg\tProject Gutenberg e-text\n\
+             0\tstring/t\tHello,\tgreeting\n",
+        )
+        .unwrap();
+        let source = Magic::load(&sources).unwrap();
+        // the extension has to say which of the compiled formats a file is
+        let database = sources.join("compiled.magicdb");
+        std::fs::write(&database, source.compile()).unwrap();
+        let misnamed = sources.join("compiled.mgc");
+        std::fs::copy(&database, &misnamed).unwrap();
+        let error = Magic::load(&misnamed).err().unwrap();
+        assert!(error.to_string().contains("name it .magicdb"));
+        let compiled = Magic::load(&database).unwrap();
+        std::fs::remove_dir_all(&sources).unwrap();
+        let mut matched = 0;
+        for entry in std::fs::read_dir("test_files").unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            let expected = classify_file_with_magic(bytes.as_slice(), &source).unwrap();
+            let actual = classify_file_with_magic(bytes.as_slice(), &compiled).unwrap();
+            assert_eq!(actual, expected);
+            matched += (expected.buffer_type == BufferType::Magic) as usize;
+        }
+        assert!(matched >= 6);
+    }
+
+    #[test]
+    fn test_endless_stream() {
+        // like `yes | file -`, which must return rather than read forever
+        let classification = classify_stream(io::repeat(b'y'), &Options::default()).unwrap();
+        assert_eq!(classification.buffer_type, BufferType::Ascii);
     }
 }
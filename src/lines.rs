@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+use crate::{BufferType, Classification, Endianness};
+
+/// Lines longer than this many characters are reported, as GNU file does.
+const LONG_LINE: usize = 300;
+const CR: u32 = 0x0D;
+const LF: u32 = 0x0A;
+const NEL: u32 = 0x85;
+
+/// The line terminators and longest line of some text.
+#[derive(Default)]
+struct Lines {
+    crlf: usize,
+    cr: usize,
+    lf: usize,
+    nel: usize,
+    /// Whether the last character was a CR, which may start a CRLF.
+    after_cr: bool,
+    /// Characters since the last line terminator.
+    current: usize,
+    longest: usize,
+}
+
+/// Appends the qualifiers GNU file puts after a text encoding, such as
+/// `with BOM` and `with CRLF line terminators`, to the description of a text
+/// classification. Only `head`, the part of the file the magic rules see,
+/// is looked at.
+pub(crate) fn qualify(classification: &mut Classification, head: &[u8]) {
+    let mut lines = Lines::default();
+    for point in points(classification, head) {
+        lines.push(point);
+    }
+    let description = &mut classification.description;
+    if classification.bom && classification.buffer_type == BufferType::Utf8 {
+        description.push_str(", with BOM");
+    }
+    lines.describe(description);
+}
+
+/// The characters of `head`, decoded as the text it was classified as.
+/// Encodings without a decoder here count each byte as a character, and
+/// bytes outside ASCII as neither terminators nor controls, since they may
+/// be part of a longer sequence.
+fn points<'a>(
+    classification: &Classification,
+    head: &'a [u8],
+) -> Box<dyn Iterator<Item = u32> + 'a> {
+    let big_endian = classification.endianness == Some(Endianness::BigEndian);
+    let bom = usize::from(classification.bom);
+    match classification.buffer_type {
+        BufferType::Utf8 => {
+            let text = head.get(3 * bom..).unwrap_or_default();
+            Box::new(
+                text.utf8_chunks()
+                    .flat_map(|chunk| chunk.valid().chars())
+                    .map(u32::from),
+            )
+        }
+        BufferType::Utf16 => {
+            let units = head.chunks_exact(2).skip(bom).map(move |unit| {
+                let unit = [unit[0], unit[1]];
+                if big_endian {
+                    u16::from_be_bytes(unit)
+                } else {
+                    u16::from_le_bytes(unit)
+                }
+            });
+            Box::new(
+                char::decode_utf16(units)
+                    .map(|point| point.map_or(char::REPLACEMENT_CHARACTER as u32, u32::from)),
+            )
+        }
+        BufferType::Utf32 => Box::new(head.chunks_exact(4).skip(bom).map(move |unit| {
+            let unit = [unit[0], unit[1], unit[2], unit[3]];
+            if big_endian {
+                u32::from_be_bytes(unit)
+            } else {
+                u32::from_le_bytes(unit)
+            }
+        })),
+        BufferType::Latin1 => Box::new(head.iter().map(|&byte| u32::from(byte))),
+        _ => Box::new(head.iter().map(|&byte| match byte {
+            0x00..=0x7F => u32::from(byte),
+            _ => char::REPLACEMENT_CHARACTER as u32,
+        })),
+    }
+}
+
+impl Lines {
+    fn push(&mut self, point: u32) {
+        match point {
+            LF if self.after_cr => self.crlf += 1,
+            LF => self.lf += 1,
+            _ if self.after_cr => self.cr += 1,
+            _ => {}
+        }
+        self.after_cr = point == CR;
+        if point == NEL {
+            self.nel += 1;
+        }
+        if matches!(point, CR | LF | NEL) {
+            self.current = 0;
+        } else {
+            self.current += 1;
+            self.longest = self.longest.max(self.current);
+        }
+    }
+
+    /// Writes the qualifiers in GNU file's words. Plain LF terminators are
+    /// the norm, so they are only listed alongside others.
+    fn describe(mut self, description: &mut String) {
+        if self.after_cr {
+            self.cr += 1;
+        }
+        if self.longest > LONG_LINE {
+            let _ = write!(description, ", with very long lines ({})", self.longest);
+        }
+        let terminators = [
+            (self.crlf, "CRLF"),
+            (self.cr, "CR"),
+            (self.lf, "LF"),
+            (self.nel, "NEL"),
+        ];
+        if terminators.iter().all(|&(count, _)| count == 0) {
+            description.push_str(", with no line terminators");
+        } else if self.crlf != 0 || self.cr != 0 || self.nel != 0 {
+            let found: Vec<_> = terminators
+                .iter()
+                .filter(|&&(count, _)| count != 0)
+                .map(|&(_, name)| name)
+                .collect();
+            let _ = write!(description, ", with {} line terminators", found.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(text: &[u8]) -> String {
+        let mut lines = Lines::default();
+        for &byte in text {
+            lines.push(byte as u32);
+        }
+        let mut description = String::new();
+        lines.describe(&mut description);
+        description
+    }
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(describe(b"one\ntwo\n"), "");
+        assert_eq!(describe(b"one"), ", with no line terminators");
+        assert_eq!(describe(b"one\r\ntwo\r\n"), ", with CRLF line terminators");
+        assert_eq!(
+            describe(b"one\r\ntwo\nthree\r"),
+            ", with CRLF, CR, LF line terminators"
+        );
+        let long = [b'x'; 1024];
+        assert_eq!(
+            describe(&long),
+            ", with very long lines (1024), with no line terminators"
+        );
+        assert_eq!(describe(b"a\x85b\n"), ", with LF, NEL line terminators");
+    }
+}
@@ -0,0 +1,437 @@
+pub(crate) mod compiled;
+pub(crate) mod database;
+pub(crate) mod evaluate;
+pub(crate) mod parse;
+pub(crate) mod rule;
+
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fs,
+    io::{Error as IOError, ErrorKind},
+    mem,
+    path::{Path, PathBuf},
+};
+
+use crate::magic::{
+    evaluate::{evaluate, Buffer, Output},
+    rule::{Kind, Rule},
+};
+
+/// A description produced by a matching magic(5) entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MagicMatch {
+    pub description: String,
+    pub mime: Option<String>,
+    pub extensions: Vec<String>,
+}
+
+/// A set of magic(5) rules, sorted the way libmagic sorts them.
+///
+/// Entries whose top-level test looks for text are kept apart and only tried
+/// once a buffer has been classified as text, exactly like GNU file.
+#[derive(Default)]
+pub struct Magic {
+    binary: Vec<Vec<Rule>>,
+    text: Vec<Vec<Rule>>,
+    named: HashMap<String, Vec<Rule>>,
+    warnings: Vec<String>,
+}
+
+impl Magic {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses magic(5) source text. Lines that cannot be parsed are skipped
+    /// along with their continuations and reported in [`Magic::warnings`].
+    pub fn parse(source: impl AsRef<[u8]>) -> Result<Self, IOError> {
+        let (rules, warnings) = parse::parse(source.as_ref());
+        let mut magic = Self::from_rules(rules);
+        magic.warnings = warnings;
+        Ok(magic)
+    }
+
+    /// Loads a database written by [`Magic::compile`], a compiled libmagic
+    /// database, a magic(5) source file, or every source file in a directory
+    /// in name order. A file named `.mgc` must be a libmagic database and
+    /// one named [`Magic::DATABASE_EXTENSION`] a database of this crate, so
+    /// neither is mistaken for the other.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IOError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let (rules, warnings) = load_directory(path)?;
+            let mut magic = Self::from_rules(rules);
+            magic.warnings = warnings;
+            return Ok(magic);
+        }
+        let bytes = fs::read(path)?;
+        let mismatch = match path.extension().and_then(OsStr::to_str) {
+            Some(LIBMAGIC_EXTENSION) if !compiled::is_compiled(&bytes) => Some(format!(
+                ".{LIBMAGIC_EXTENSION} files must be compiled libmagic databases{}",
+                if database::is_database(&bytes) {
+                    format!(
+                        ", and this one was compiled by this program; name it .{}",
+                        Self::DATABASE_EXTENSION
+                    )
+                } else {
+                    String::new()
+                }
+            )),
+            Some(Self::DATABASE_EXTENSION) if !database::is_database(&bytes) => Some(format!(
+                ".{} files must be databases compiled by this program{}",
+                Self::DATABASE_EXTENSION,
+                if compiled::is_compiled(&bytes) {
+                    format!(", and this one is a libmagic database; name it .{LIBMAGIC_EXTENSION}")
+                } else {
+                    String::new()
+                }
+            )),
+            _ => None,
+        };
+        if let Some(message) = mismatch {
+            return Err(IOError::new(ErrorKind::InvalidData, message));
+        }
+        let mut magic = Self::from_bytes(bytes)?;
+        for warning in &mut magic.warnings {
+            *warning = format!("{}: {warning}", path.display());
+        }
+        Ok(magic)
+    }
+
+    /// Like [`Magic::load`], for a database or source already in memory.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, IOError> {
+        let bytes = bytes.as_ref();
+        if database::is_database(bytes) {
+            let (binary, text, named) = database::decode(bytes)?;
+            return Ok(Self {
+                binary,
+                text,
+                named: named.into_iter().collect(),
+                warnings: Vec::new(),
+            });
+        }
+        if compiled::is_compiled(bytes) {
+            let sets = compiled::parse(bytes)?;
+            return Ok(Self::from_sets(sets.binary, sets.text, sets.named));
+        }
+        Self::parse(bytes)
+    }
+
+    /// The extension of files written from [`Magic::compile`], kept apart
+    /// from libmagic's `.mgc` since libmagic cannot read them.
+    pub const DATABASE_EXTENSION: &'static str = "magicdb";
+
+    /// Serialises the set into the compact form read back by
+    /// [`Magic::load`]. Entries are stored already sorted by strength, so
+    /// loading it skips both parsing and sorting.
+    ///
+    /// The database starts with the bytes `FRSM` and then a little-endian
+    /// `u32` format version, currently 1. Neither libmagic's magic number
+    /// nor its version can be read there, and a database of another version
+    /// is rejected when loaded rather than misread.
+    pub fn compile(&self) -> Vec<u8> {
+        let mut named: Vec<_> = self.named.iter().collect();
+        named.sort_unstable_by_key(|(name, _)| *name);
+        database::encode(&database::Sets {
+            binary: &self.binary,
+            text: &self.text,
+            named,
+        })
+    }
+
+    /// Loads a colon separated list of databases, as accepted by `-m` and the
+    /// `MAGIC` environment variable. A path that does not exist is tried
+    /// again with [`Magic::DATABASE_EXTENSION`] and then `.mgc` appended,
+    /// like libmagic does with `.mgc`.
+    pub fn load_list(list: impl AsRef<OsStr>) -> Result<Self, IOError> {
+        let mut magic = Self::new();
+        for path in env::split_paths(list.as_ref()) {
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            let compiled = [Self::DATABASE_EXTENSION, LIBMAGIC_EXTENSION]
+                .into_iter()
+                .map(|extension| {
+                    let mut compiled = path.clone().into_os_string();
+                    compiled.push(".");
+                    compiled.push(extension);
+                    PathBuf::from(compiled)
+                })
+                .find(|compiled| compiled.exists());
+            magic.extend(
+                if let Some(compiled) = compiled.filter(|_| !path.exists()) {
+                    Self::load(compiled)?
+                } else {
+                    Self::load(&path).map_err(|error| {
+                        IOError::new(error.kind(), format!("{}: {error}", path.display()))
+                    })?
+                },
+            );
+        }
+        Ok(magic)
+    }
+
+    /// Loads the database named by the `MAGIC` environment variable, or the
+    /// first compiled database found in the usual system locations. Returns
+    /// an empty set when there is none.
+    pub fn system() -> Result<Self, IOError> {
+        if let Some(list) = env::var_os("MAGIC") {
+            return Self::load_list(list);
+        }
+        SYSTEM_DATABASES
+            .iter()
+            .map(Path::new)
+            .find(|path| path.is_file())
+            .map_or_else(|| Ok(Self::new()), Self::load)
+    }
+
+    /// Adds every entry of `other`, keeping the combined set in strength
+    /// order. Named subroutines already present take precedence.
+    pub fn extend(&mut self, mut other: Magic) {
+        self.warnings.append(&mut other.warnings);
+        if self.is_empty() && self.named.is_empty() {
+            other.warnings = mem::take(&mut self.warnings);
+            *self = other;
+            return;
+        }
+        self.binary.extend(other.binary);
+        self.text.extend(other.text);
+        for (name, entry) in other.named {
+            self.named.entry(name).or_insert(entry);
+        }
+        sort_by_strength(&mut self.binary);
+        sort_by_strength(&mut self.text);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.binary.is_empty() && self.text.is_empty()
+    }
+
+    /// The number of top-level entries, not counting named subroutines.
+    pub fn len(&self) -> usize {
+        self.binary.len() + self.text.len()
+    }
+
+    /// The source lines that were skipped because they could not be parsed,
+    /// each naming its file and line number.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Runs every entry that does not depend on the buffer being text,
+    /// returning the first that produces a description.
+    pub fn identify(&self, buffer: &[u8]) -> Option<MagicMatch> {
+        self.identify_binary(buffer, true, false)
+    }
+
+    /// Runs the binary entries, leaving out those marked `/b` when the
+    /// buffer looks like text.
+    pub(crate) fn identify_binary(
+        &self,
+        data: &[u8],
+        complete: bool,
+        text: bool,
+    ) -> Option<MagicMatch> {
+        let entries = self
+            .binary
+            .iter()
+            .filter(|entry| !(text && entry[0].is_binary_test()));
+        self.first_match(entries, Buffer { data, complete })
+    }
+
+    pub(crate) fn identify_text(&self, data: &[u8], complete: bool) -> Option<MagicMatch> {
+        self.first_match(&self.text, Buffer { data, complete })
+    }
+
+    /// Builds a set from rules in source order, deciding which entries are
+    /// text tests and sorting by strength.
+    pub(crate) fn from_rules(rules: Vec<Rule>) -> Self {
+        let mut magic = Self::default();
+        for entry in split_entries(rules) {
+            if let Kind::Name(name) = &entry[0].kind {
+                magic.named.entry(name.clone()).or_insert(entry);
+            } else if entry[0].is_text_test() {
+                magic.text.push(entry);
+            } else {
+                magic.binary.push(entry);
+            }
+        }
+        sort_by_strength(&mut magic.binary);
+        sort_by_strength(&mut magic.text);
+        magic
+    }
+
+    /// Builds a set from rules that are already split and ordered, as they
+    /// are in a compiled database.
+    pub(crate) fn from_sets(binary: Vec<Rule>, text: Vec<Rule>, named: Vec<Rule>) -> Self {
+        let mut magic = Self {
+            binary: split_entries(binary),
+            text: split_entries(text),
+            ..Self::default()
+        };
+        for entry in split_entries(named) {
+            if let Kind::Name(name) = &entry[0].kind {
+                magic.named.entry(name.clone()).or_insert(entry);
+            }
+        }
+        magic
+    }
+
+    fn first_match<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a Vec<Rule>>,
+        buffer: Buffer,
+    ) -> Option<MagicMatch> {
+        entries.into_iter().find_map(|entry| {
+            let mut output = Output::default();
+            let matched = evaluate(self, entry, buffer, 0, false, 0, &mut output);
+            if !matched || output.description.is_empty() {
+                return None;
+            }
+            Some(MagicMatch {
+                description: output.description,
+                mime: output.mime,
+                extensions: output.extensions,
+            })
+        })
+    }
+}
+
+/// The extension of compiled libmagic databases.
+const LIBMAGIC_EXTENSION: &str = "mgc";
+
+const SYSTEM_DATABASES: [&str; 3] = [
+    "/usr/share/misc/magic.mgc",
+    "/usr/share/file/magic.mgc",
+    "/usr/lib/file/magic.mgc",
+];
+
+fn split_entries(rules: Vec<Rule>) -> Vec<Vec<Rule>> {
+    let mut entries: Vec<Vec<Rule>> = Vec::new();
+    for rule in rules {
+        match entries.last_mut() {
+            Some(entry) if rule.level > 0 => entry.push(rule),
+            _ if rule.level > 0 => {}
+            _ => entries.push(vec![rule]),
+        }
+    }
+    entries
+}
+
+fn sort_by_strength(entries: &mut [Vec<Rule>]) {
+    entries.sort_by_cached_key(|entry| std::cmp::Reverse(entry[0].strength()));
+}
+
+fn load_directory(path: &Path) -> Result<(Vec<Rule>, Vec<String>), IOError> {
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    let mut rules = Vec::new();
+    let mut warnings = Vec::new();
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let bytes = fs::read(path)?;
+        if compiled::is_compiled(&bytes) || database::is_database(&bytes) {
+            return Err(IOError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{}: compiled databases cannot be mixed with sources",
+                    path.display()
+                ),
+            ));
+        }
+        let (parsed, skipped) = parse::parse(&bytes);
+        rules.extend(parsed);
+        warnings.extend(
+            skipped
+                .into_iter()
+                .map(|warning| format!("{}: {warning}", path.display())),
+        );
+    }
+    Ok((rules, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+0\tstring\t\\x7fELF\tELF
+!:mime\tapplication/x-executable
+>4\tbyte\t1\t32-bit
+>4\tbyte\t2\t64-bit
+>>5\tbyte\t1\tLSB
+>>5\tdefault\tx\tunknown endianness
+0\tname\tversion
+>0\tubyte\tx\t\\b, version %u
+0\tstring\tPK\\x03\\x04\tZip archive data
+>4\tuse\tversion
+0\tsearch/64\t#!/bin/sh\tPOSIX shell script
+";
+
+    #[test]
+    fn test_identify() {
+        let magic = Magic::parse(SOURCE).unwrap();
+        assert_eq!(magic.len(), 3);
+        let elf = magic.identify(b"\x7fELF\x02\x01rest").unwrap();
+        assert_eq!(elf.description, "ELF 64-bit LSB");
+        assert_eq!(elf.mime.as_deref(), Some("application/x-executable"));
+        let unknown = magic.identify(b"\x7fELF\x02\x02").unwrap();
+        assert_eq!(unknown.description, "ELF 64-bit unknown endianness");
+        let narrow = magic.identify(b"\x7fELF\x01\x02").unwrap();
+        assert_eq!(narrow.description, "ELF 32-bit");
+        let zip = magic.identify(b"PK\x03\x04\x14\x00").unwrap();
+        assert_eq!(zip.description, "Zip archive data, version 20");
+        assert!(magic.identify(b"#!/bin/sh\necho").is_none());
+        let script = magic.identify_text(b"#!/bin/sh\necho", true).unwrap();
+        assert_eq!(script.description, "POSIX shell script");
+    }
+
+    #[test]
+    fn test_binary_only() {
+        let magic =
+            Magic::parse("0\tstring/b\t#!\\ /bin/sh\tshell script (binary data)\n").unwrap();
+        let script = b"#! /bin/sh\necho";
+        assert!(magic.identify_binary(script, true, false).is_some());
+        assert!(magic.identify_binary(script, true, true).is_none());
+    }
+
+    #[test]
+    fn test_bad_line() {
+        let source = SOURCE.replace("0\tname", "0\tquadbyte\t1\tbad\n0\tname");
+        let magic = Magic::parse(source).unwrap();
+        assert_eq!(magic.warnings(), ["magic line 7: unknown type `quadbyte`"]);
+        assert_eq!(magic.len(), 3);
+        assert_eq!(
+            magic.identify(b"\x7fELF\x02\x01").unwrap().description,
+            "ELF 64-bit LSB"
+        );
+        assert_eq!(
+            magic.identify(b"PK\x03\x04\x14\x00").unwrap().description,
+            "Zip archive data, version 20"
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        let magic = Magic::parse(SOURCE).unwrap();
+        let compiled = Magic::from_bytes(magic.compile()).unwrap();
+        assert_eq!(compiled.binary, magic.binary);
+        assert_eq!(compiled.text, magic.text);
+        assert_eq!(compiled.named, magic.named);
+        assert_eq!(compiled.compile(), magic.compile());
+    }
+
+    #[test]
+    fn test_strength_ordering() {
+        let magic =
+            Magic::parse("0\tbyte\t0x50\tshort match\n0\tstring\tPK\\x03\\x04\tlong match\n")
+                .unwrap();
+        assert_eq!(
+            magic.identify(b"PK\x03\x04").unwrap().description,
+            "long match"
+        );
+    }
+}
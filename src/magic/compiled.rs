@@ -0,0 +1,366 @@
+use std::io::{Error as IOError, ErrorKind};
+
+use crate::magic::rule::*;
+
+pub(crate) const MAGIC_NUMBER: u32 = 0xF11E_041C;
+const VERSION: u32 = 18;
+const ENTRY_SIZE: usize = 376;
+
+const INDIR: u8 = 0x01;
+const OFFADD: u8 = 0x02;
+const INDIROFFADD: u8 = 0x04;
+const UNSIGNED: u8 = 0x08;
+const NOSPACE: u8 = 0x10;
+const BINTEST: u8 = 0x20;
+const TEXTTEST: u8 = 0x40;
+const OFFNEGATIVE: u8 = 0x80;
+
+const OP_MASK: u8 = 0x07;
+const OP_SIGNED: u8 = 0x20;
+const OP_INVERSE: u8 = 0x40;
+const OP_INDIRECT: u8 = 0x80;
+
+pub(crate) fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.len() >= 4
+        && (bytes[..4] == MAGIC_NUMBER.to_le_bytes() || bytes[..4] == MAGIC_NUMBER.to_be_bytes())
+}
+
+struct Record<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Record<'_> {
+    fn u8(&self, at: usize) -> u8 {
+        self.bytes[at]
+    }
+
+    fn u16(&self, at: usize) -> u16 {
+        let bytes = [self.bytes[at], self.bytes[at + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, at: usize) -> u32 {
+        let bytes = self.bytes[at..at + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u64(&self, at: usize) -> u64 {
+        let bytes = self.bytes[at..at + 8].try_into().unwrap();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+
+    fn text(&self, at: usize, length: usize) -> Option<String> {
+        let field = &self.bytes[at..at + length];
+        let end = field.iter().position(|&byte| byte == 0).unwrap_or(length);
+        (end > 0).then(|| String::from_utf8_lossy(&field[..end]).into_owned())
+    }
+}
+
+/// The rules of a compiled database, in the order libmagic stored them.
+pub(crate) struct Sets {
+    pub(crate) binary: Vec<Rule>,
+    pub(crate) text: Vec<Rule>,
+    pub(crate) named: Vec<Rule>,
+}
+
+/// Reads a libmagic compiled database (the `magic.mgc` format, version 18).
+///
+/// libmagic keeps `name` entries in the second set and every other entry in
+/// the first, where each top-level record is flagged as a binary or a text
+/// test; its continuations follow it into the same set.
+pub(crate) fn parse(bytes: &[u8]) -> Result<Sets, IOError> {
+    let invalid = |message: &str| IOError::new(ErrorKind::InvalidData, message.to_owned());
+    if bytes.len() < ENTRY_SIZE || !is_compiled(bytes) {
+        return Err(invalid("not a compiled magic database"));
+    }
+    let header = Record {
+        bytes: &bytes[..ENTRY_SIZE],
+        big_endian: bytes[..4] == MAGIC_NUMBER.to_be_bytes(),
+    };
+    let version = header.u32(4);
+    if version != VERSION {
+        return Err(IOError::new(
+            ErrorKind::InvalidData,
+            format!("unsupported compiled magic version {version}, expected {VERSION}"),
+        ));
+    }
+    let tests_count = header.u32(8) as usize;
+    let named_count = header.u32(12) as usize;
+    let records = &bytes[ENTRY_SIZE..];
+    if records.len() / ENTRY_SIZE < tests_count + named_count {
+        return Err(invalid("truncated compiled magic database"));
+    }
+    let mut records = records
+        .chunks_exact(ENTRY_SIZE)
+        .take(tests_count + named_count)
+        .map(|bytes| Record {
+            bytes,
+            big_endian: header.big_endian,
+        });
+    let mut sets = Sets {
+        binary: Vec::new(),
+        text: Vec::new(),
+        named: Vec::new(),
+    };
+    let mut text = false;
+    for record in records.by_ref().take(tests_count) {
+        if record.u16(0) == 0 {
+            text = record.u8(2) & (TEXTTEST | BINTEST) == TEXTTEST;
+        }
+        let rule = convert(&record);
+        if text {
+            sets.text.push(rule);
+        } else {
+            sets.binary.push(rule);
+        }
+    }
+    sets.named = records.map(|record| convert(&record)).collect();
+    Ok(sets)
+}
+
+fn number_type(code: u8) -> Option<Number> {
+    use Endian::*;
+    use NumberFormat::*;
+    Some(match code {
+        1 => Number::new(1, Native, Integer),
+        2 => Number::new(2, Native, Integer),
+        4 => Number::new(4, Native, Integer),
+        6 => Number::new(4, Native, Date),
+        7 => Number::new(2, Big, Integer),
+        8 => Number::new(4, Big, Integer),
+        9 => Number::new(4, Big, Date),
+        10 => Number::new(2, Little, Integer),
+        11 => Number::new(4, Little, Integer),
+        12 => Number::new(4, Little, Date),
+        14 => Number::new(4, Native, LocalDate),
+        15 => Number::new(4, Big, LocalDate),
+        16 => Number::new(4, Little, LocalDate),
+        21 => Number::new(4, Middle, Date),
+        22 => Number::new(4, Middle, LocalDate),
+        23 => Number::new(4, Middle, Integer),
+        24 => Number::new(8, Native, Integer),
+        25 => Number::new(8, Little, Integer),
+        26 => Number::new(8, Big, Integer),
+        27 => Number::new(8, Native, Date),
+        28 => Number::new(8, Little, Date),
+        29 => Number::new(8, Big, Date),
+        30 => Number::new(8, Native, LocalDate),
+        31 => Number::new(8, Little, LocalDate),
+        32 => Number::new(8, Big, LocalDate),
+        33 => Number::new(4, Native, Float),
+        34 => Number::new(4, Big, Float),
+        35 => Number::new(4, Little, Float),
+        36 => Number::new(8, Native, Float),
+        37 => Number::new(8, Big, Float),
+        38 => Number::new(8, Little, Float),
+        39 => Number::new(4, Big, Id3),
+        40 => Number::new(4, Little, Id3),
+        42 => Number::new(8, Native, WindowsDate),
+        43 => Number::new(8, Little, WindowsDate),
+        44 => Number::new(8, Big, WindowsDate),
+        _ => return None,
+    })
+}
+
+fn operator(code: u8) -> Operator {
+    match code & OP_MASK {
+        0 => Operator::And,
+        1 => Operator::Or,
+        2 => Operator::Xor,
+        3 => Operator::Add,
+        4 => Operator::Subtract,
+        5 => Operator::Multiply,
+        6 => Operator::Divide,
+        _ => Operator::Modulo,
+    }
+}
+
+fn convert(record: &Record) -> Rule {
+    let flag = record.u8(2);
+    let factor = record.u8(3);
+    let relation = match record.u8(4) {
+        b'!' => Relation::NotEqual,
+        b'<' => Relation::Less,
+        b'>' => Relation::Greater,
+        b'&' => Relation::AllSet,
+        b'^' => Relation::AnyClear,
+        b'x' => Relation::Any,
+        _ => Relation::Equal,
+    };
+    let value_length = (record.u8(5) as usize).min(128);
+    let type_code = record.u8(6);
+    let in_type = record.u8(7);
+    let in_op = record.u8(8);
+    let mask_op = record.u8(9);
+    let strength_op = match record.u8(11) {
+        b'+' => Some(StrengthOp::Add),
+        b'-' => Some(StrengthOp::Subtract),
+        b'*' => Some(StrengthOp::Multiply),
+        b'/' => Some(StrengthOp::Divide),
+        _ => None,
+    };
+    let raw_offset = record.u32(12) as i32 as i64;
+    let in_offset = record.u32(16) as i32 as i64;
+    let mask = record.u64(24);
+    let count = record.u32(24);
+    let string_flags = record.u32(28);
+    let value_bytes = record.bytes[32..32 + value_length].to_vec();
+    let value_name = || String::from_utf8_lossy(&value_bytes).into_owned();
+
+    let kind = match type_code {
+        3 => Kind::Default,
+        5 => Kind::String {
+            flags: string_flags,
+        },
+        13 => Kind::PString {
+            flags: string_flags,
+        },
+        18 => Kind::String16 {
+            endian: Endian::Big,
+        },
+        19 => Kind::String16 {
+            endian: Endian::Little,
+        },
+        20 => Kind::Search {
+            range: count,
+            flags: string_flags,
+        },
+        45 => Kind::Name(value_name()),
+        46 => match value_name().strip_prefix('^') {
+            Some(name) => Kind::Use {
+                name: name.to_owned(),
+                flip: true,
+            },
+            None => Kind::Use {
+                name: value_name(),
+                flip: false,
+            },
+        },
+        47 => Kind::Clear,
+        code => match number_type(code) {
+            Some(number) => Kind::Numeric {
+                number,
+                signed: flag & UNSIGNED == 0,
+            },
+            None => Kind::Unsupported,
+        },
+    };
+    let value = match &kind {
+        Kind::Numeric { number, .. } if number.format == NumberFormat::Float => {
+            if number.width == 4 {
+                Value::Float(f32::from_bits(record.u32(32)) as f64)
+            } else {
+                Value::Float(f64::from_bits(record.u64(32)))
+            }
+        }
+        Kind::Numeric { .. } | Kind::Default | Kind::Clear | Kind::Name(_) | Kind::Use { .. } => {
+            Value::Number(record.u64(32))
+        }
+        _ => Value::Bytes(value_bytes.clone()),
+    };
+    let numeric = matches!(kind, Kind::Numeric { .. });
+
+    let magnitude = if flag & OFFNEGATIVE != 0 {
+        -raw_offset
+    } else {
+        raw_offset
+    };
+    let indirect_number = number_type(in_type);
+    let indirect = (flag & INDIR != 0).then(|| Indirect {
+        number: indirect_number.unwrap_or(Number::new(4, Endian::Native, NumberFormat::Integer)),
+        signed: in_op & OP_SIGNED != 0,
+        operator: Some(operator(in_op)),
+        operand: in_offset,
+        operand_indirect: in_op & OP_INDIRECT != 0,
+        inverse: in_op & OP_INVERSE != 0,
+        relative_result: flag & INDIROFFADD != 0,
+    });
+    let offset = Offset {
+        value: magnitude,
+        relative: flag & OFFADD != 0,
+        from_end: flag & OFFNEGATIVE != 0 && flag & OFFADD == 0,
+        indirect,
+    };
+    Rule {
+        level: record.u16(0),
+        offset,
+        kind: if indirect.is_some() && indirect_number.is_none() {
+            Kind::Unsupported
+        } else {
+            kind
+        },
+        mask: (numeric && mask != 0).then_some((operator(mask_op), mask)),
+        inverse: numeric && mask_op & OP_INVERSE != 0,
+        relation,
+        value,
+        description: record.text(160, 64).unwrap_or_default(),
+        no_space: flag & NOSPACE != 0,
+        mime: record.text(224, 80),
+        extensions: record
+            .text(312, 64)
+            .map(|extensions| {
+                extensions
+                    .split('/')
+                    .filter(|extension| !extension.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        strength: strength_op.map(|op| (op, factor)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: u16, flag: u8, type_code: u8, value: &[u8], description: &str) -> Vec<u8> {
+        let mut record = vec![0; ENTRY_SIZE];
+        record[0..2].copy_from_slice(&level.to_le_bytes());
+        record[2] = flag;
+        record[4] = b'=';
+        record[5] = value.len() as u8;
+        record[6] = type_code;
+        record[32..32 + value.len()].copy_from_slice(value);
+        record[160..160 + description.len()].copy_from_slice(description.as_bytes());
+        record
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut bytes = vec![0; ENTRY_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        bytes[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[8..12].copy_from_slice(&4u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&1u32.to_le_bytes());
+        bytes.extend(record(0, BINTEST, 5, b"PK\x03\x04", "Zip archive data"));
+        bytes.extend(record(1, 0, 1, &[0x14, 0, 0, 0, 0, 0, 0, 0], "\\b, v2.0"));
+        bytes.extend(record(0, TEXTTEST, 5, b"#!/bin/sh", "POSIX shell script"));
+        bytes.extend(record(1, 0, 5, b"-e", "\\b, errexit"));
+        bytes.extend(record(0, 0, 45, b"header", ""));
+        let sets = parse(&bytes).unwrap();
+        assert_eq!(sets.binary.len(), 2);
+        assert_eq!(sets.binary[0].value, Value::Bytes(b"PK\x03\x04".to_vec()));
+        assert_eq!(sets.binary[1].level, 1);
+        assert_eq!(sets.binary[1].value, Value::Number(0x14));
+        assert_eq!(sets.text.len(), 2);
+        assert_eq!(sets.text[0].description, "POSIX shell script");
+        assert_eq!(sets.text[1].level, 1);
+        assert_eq!(sets.named.len(), 1);
+        assert_eq!(sets.named[0].kind, Kind::Name("header".to_owned()));
+        assert!(parse(&bytes[..ENTRY_SIZE + 10]).is_err());
+    }
+}
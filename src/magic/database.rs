@@ -0,0 +1,451 @@
+use std::io::{Error as IOError, ErrorKind};
+
+use crate::magic::rule::*;
+
+/// Identifies databases written by [`encode`]. Deliberately different from
+/// libmagic's number so the two compiled formats can never be confused.
+const MAGIC_NUMBER: [u8; 4] = *b"FRSM";
+const VERSION: u32 = 1;
+
+pub(crate) fn is_database(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC_NUMBER)
+}
+
+/// The sets stored in a database: binary and text entries already in
+/// strength order, then named subroutines sorted by name.
+pub(crate) struct Sets<'a> {
+    pub(crate) binary: &'a [Vec<Rule>],
+    pub(crate) text: &'a [Vec<Rule>],
+    pub(crate) named: Vec<(&'a String, &'a Vec<Rule>)>,
+}
+
+pub(crate) type Decoded = (Vec<Vec<Rule>>, Vec<Vec<Rule>>, Vec<(String, Vec<Rule>)>);
+
+pub(crate) fn encode(sets: &Sets) -> Vec<u8> {
+    let mut encoder = Encoder(MAGIC_NUMBER.to_vec());
+    encoder.u32(VERSION);
+    for entries in [sets.binary, sets.text] {
+        encoder.u32(entries.len() as u32);
+        for entry in entries {
+            encoder.entry(entry);
+        }
+    }
+    encoder.u32(sets.named.len() as u32);
+    for (name, entry) in &sets.named {
+        encoder.bytes(name.as_bytes());
+        encoder.entry(entry);
+    }
+    encoder.0
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Decoded, IOError> {
+    let invalid = |message: &str| IOError::new(ErrorKind::InvalidData, message.to_owned());
+    if !is_database(bytes) {
+        return Err(invalid("not a compiled magic database"));
+    }
+    let mut decoder = Decoder {
+        bytes,
+        position: MAGIC_NUMBER.len(),
+    };
+    let version = decoder
+        .u32()
+        .ok_or_else(|| invalid("truncated compiled magic database"))?;
+    if version != VERSION {
+        return Err(IOError::new(
+            ErrorKind::InvalidData,
+            format!("unsupported compiled magic version {version}, expected {VERSION}"),
+        ));
+    }
+    decoder
+        .sets()
+        .ok_or_else(|| invalid("corrupt compiled magic database"))
+}
+
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn optional_bytes(&mut self, bytes: Option<&[u8]>) {
+        self.bool(bytes.is_some());
+        if let Some(bytes) = bytes {
+            self.bytes(bytes);
+        }
+    }
+
+    fn entry(&mut self, entry: &[Rule]) {
+        self.u32(entry.len() as u32);
+        for rule in entry {
+            self.rule(rule);
+        }
+    }
+
+    fn number(&mut self, number: Number) {
+        self.u8(number.width);
+        self.u8(number.endian as u8);
+        self.u8(number.format as u8);
+    }
+
+    fn rule(&mut self, rule: &Rule) {
+        self.u16(rule.level);
+        self.u64(rule.offset.value as u64);
+        self.bool(rule.offset.relative);
+        self.bool(rule.offset.from_end);
+        self.bool(rule.offset.indirect.is_some());
+        if let Some(indirect) = rule.offset.indirect {
+            self.number(indirect.number);
+            self.bool(indirect.signed);
+            self.u8(indirect.operator.map_or(u8::MAX, |operator| operator as u8));
+            self.u64(indirect.operand as u64);
+            self.bool(indirect.operand_indirect);
+            self.bool(indirect.inverse);
+            self.bool(indirect.relative_result);
+        }
+        match &rule.kind {
+            Kind::Numeric { number, signed } => {
+                self.u8(0);
+                self.number(*number);
+                self.bool(*signed);
+            }
+            Kind::String { flags } => {
+                self.u8(1);
+                self.u32(*flags);
+            }
+            Kind::String16 { endian } => {
+                self.u8(2);
+                self.u8(*endian as u8);
+            }
+            Kind::PString { flags } => {
+                self.u8(3);
+                self.u32(*flags);
+            }
+            Kind::Search { range, flags } => {
+                self.u8(4);
+                self.u32(*range);
+                self.u32(*flags);
+            }
+            Kind::Default => self.u8(5),
+            Kind::Clear => self.u8(6),
+            Kind::Name(name) => {
+                self.u8(7);
+                self.bytes(name.as_bytes());
+            }
+            Kind::Use { name, flip } => {
+                self.u8(8);
+                self.bytes(name.as_bytes());
+                self.bool(*flip);
+            }
+            Kind::Unsupported => self.u8(9),
+        }
+        self.bool(rule.mask.is_some());
+        if let Some((operator, operand)) = rule.mask {
+            self.u8(operator as u8);
+            self.u64(operand);
+        }
+        self.bool(rule.inverse);
+        self.u8(rule.relation as u8);
+        match &rule.value {
+            Value::Number(value) => {
+                self.u8(0);
+                self.u64(*value);
+            }
+            Value::Float(value) => {
+                self.u8(1);
+                self.u64(value.to_bits());
+            }
+            Value::Bytes(bytes) => {
+                self.u8(2);
+                self.bytes(bytes);
+            }
+        }
+        self.bytes(rule.description.as_bytes());
+        self.bool(rule.no_space);
+        self.optional_bytes(rule.mime.as_ref().map(String::as_bytes));
+        self.u32(rule.extensions.len() as u32);
+        for extension in &rule.extensions {
+            self.bytes(extension.as_bytes());
+        }
+        self.bool(rule.strength.is_some());
+        if let Some((op, factor)) = rule.strength {
+            self.u8(op as u8);
+            self.u8(factor);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.bytes.get(self.position..self.position + N)?;
+        self.position += N;
+        bytes.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        let length = self.u32()? as usize;
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.checked_add(length)?)?;
+        self.position += length;
+        Some(bytes.to_vec())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?).ok()
+    }
+
+    fn sets(&mut self) -> Option<Decoded> {
+        let binary = self.entries()?;
+        let text = self.entries()?;
+        let named = (0..self.u32()?)
+            .map(|_| Some((self.string()?, self.entry()?)))
+            .collect::<Option<_>>()?;
+        (self.position == self.bytes.len()).then_some((binary, text, named))
+    }
+
+    fn entries(&mut self) -> Option<Vec<Vec<Rule>>> {
+        (0..self.u32()?).map(|_| self.entry()).collect()
+    }
+
+    fn entry(&mut self) -> Option<Vec<Rule>> {
+        let entry: Vec<Rule> = (0..self.u32()?)
+            .map(|_| self.rule())
+            .collect::<Option<_>>()?;
+        (entry.first()?.level == 0).then_some(entry)
+    }
+
+    fn endian(&mut self) -> Option<Endian> {
+        Some(match self.u8()? {
+            0 => Endian::Native,
+            1 => Endian::Little,
+            2 => Endian::Big,
+            3 => Endian::Middle,
+            _ => return None,
+        })
+    }
+
+    fn number(&mut self) -> Option<Number> {
+        let width = self.u8()?;
+        let endian = self.endian()?;
+        let format = match self.u8()? {
+            0 => NumberFormat::Integer,
+            1 => NumberFormat::Float,
+            2 => NumberFormat::Date,
+            3 => NumberFormat::LocalDate,
+            4 => NumberFormat::WindowsDate,
+            5 => NumberFormat::Id3,
+            _ => return None,
+        };
+        Some(Number::new(width, endian, format))
+    }
+
+    fn rule(&mut self) -> Option<Rule> {
+        let level = self.u16()?;
+        let value = self.u64()? as i64;
+        let relative = self.bool()?;
+        let from_end = self.bool()?;
+        let indirect = if self.bool()? {
+            Some(Indirect {
+                number: self.number()?,
+                signed: self.bool()?,
+                operator: match self.u8()? {
+                    u8::MAX => None,
+                    code => Some(operator(code)?),
+                },
+                operand: self.u64()? as i64,
+                operand_indirect: self.bool()?,
+                inverse: self.bool()?,
+                relative_result: self.bool()?,
+            })
+        } else {
+            None
+        };
+        let kind = match self.u8()? {
+            0 => Kind::Numeric {
+                number: self.number()?,
+                signed: self.bool()?,
+            },
+            1 => Kind::String { flags: self.u32()? },
+            2 => Kind::String16 {
+                endian: self.endian()?,
+            },
+            3 => Kind::PString { flags: self.u32()? },
+            4 => Kind::Search {
+                range: self.u32()?,
+                flags: self.u32()?,
+            },
+            5 => Kind::Default,
+            6 => Kind::Clear,
+            7 => Kind::Name(self.string()?),
+            8 => Kind::Use {
+                name: self.string()?,
+                flip: self.bool()?,
+            },
+            9 => Kind::Unsupported,
+            _ => return None,
+        };
+        let mask = if self.bool()? {
+            Some((operator(self.u8()?)?, self.u64()?))
+        } else {
+            None
+        };
+        let inverse = self.bool()?;
+        let relation = match self.u8()? {
+            0 => Relation::Equal,
+            1 => Relation::NotEqual,
+            2 => Relation::Less,
+            3 => Relation::Greater,
+            4 => Relation::AllSet,
+            5 => Relation::AnyClear,
+            6 => Relation::Any,
+            _ => return None,
+        };
+        let test_value = match self.u8()? {
+            0 => Value::Number(self.u64()?),
+            1 => Value::Float(f64::from_bits(self.u64()?)),
+            2 => Value::Bytes(self.bytes()?),
+            _ => return None,
+        };
+        let description = self.string()?;
+        let no_space = self.bool()?;
+        let mime = if self.bool()? {
+            Some(self.string()?)
+        } else {
+            None
+        };
+        let extensions = (0..self.u32()?)
+            .map(|_| self.string())
+            .collect::<Option<_>>()?;
+        let strength = if self.bool()? {
+            let op = match self.u8()? {
+                0 => StrengthOp::Add,
+                1 => StrengthOp::Subtract,
+                2 => StrengthOp::Multiply,
+                3 => StrengthOp::Divide,
+                _ => return None,
+            };
+            Some((op, self.u8()?))
+        } else {
+            None
+        };
+        Some(Rule {
+            level,
+            offset: Offset {
+                value,
+                relative,
+                from_end,
+                indirect,
+            },
+            kind,
+            mask,
+            inverse,
+            relation,
+            value: test_value,
+            description,
+            no_space,
+            mime,
+            extensions,
+            strength,
+        })
+    }
+}
+
+fn operator(code: u8) -> Option<Operator> {
+    Some(match code {
+        0 => Operator::And,
+        1 => Operator::Or,
+        2 => Operator::Xor,
+        3 => Operator::Add,
+        4 => Operator::Subtract,
+        5 => Operator::Multiply,
+        6 => Operator::Divide,
+        7 => Operator::Modulo,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magic::parse::parse;
+
+    #[test]
+    fn test_round_trip() {
+        let (rules, warnings) = parse(
+            b"0\tstring/c\tPK\\x03\\x04\tZip archive data\n\
+              !:mime\tapplication/zip\n\
+              !:ext\tzip/jar\n\
+              !:strength\t+10\n\
+              >(4.s*2)\tlelong&0xff\t<3\tsmall\n\
+              >>&-2\tlefloat\t1.5\tfloat\n\
+              >-4\tsearch/16\tend\tat the end\n\
+              0\tname\tsub\n\
+              >0\tbyte\tx\t%d\n",
+        );
+        assert!(warnings.is_empty());
+        let entries = [rules[..4].to_vec()];
+        let (name, subroutine) = (String::from("sub"), rules[4..].to_vec());
+        let sets = Sets {
+            binary: &entries,
+            text: &[],
+            named: vec![(&name, &subroutine)],
+        };
+        let bytes = encode(&sets);
+        let (binary, text, decoded_named) = decode(&bytes).unwrap();
+        assert_eq!(binary, entries);
+        assert!(text.is_empty());
+        assert_eq!(decoded_named, vec![(name, subroutine)]);
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,860 @@
+use crate::magic::{rule::*, Magic};
+
+const MAX_USE_DEPTH: u8 = 32;
+const MAX_STRING_LENGTH: usize = 127;
+
+/// The bytes a rule set is evaluated against. `complete` records whether
+/// `data` holds the whole file, which negative (end-relative) offsets need.
+#[derive(Copy, Clone)]
+pub(crate) struct Buffer<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) complete: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Output {
+    pub(crate) description: String,
+    pub(crate) mime: Option<String>,
+    pub(crate) extensions: Vec<String>,
+    need_separator: bool,
+    found: bool,
+}
+
+enum Printable {
+    Integer { value: u64, width: u8 },
+    Float(f64),
+    Text(Vec<u8>),
+}
+
+struct Matched {
+    end: usize,
+    printable: Printable,
+}
+
+/// Evaluates one entry — a top-level rule and its continuations — against the
+/// buffer the way libmagic's `match()` does, appending to `output` as rules
+/// match. Returns whether the top-level rule matched.
+pub(crate) fn evaluate(
+    magic: &Magic,
+    rules: &[Rule],
+    buffer: Buffer,
+    base: usize,
+    flip: bool,
+    depth: u8,
+    output: &mut Output,
+) -> bool {
+    let Some(first) = rules.first() else {
+        return false;
+    };
+    let Some(matched) = test(magic, first, buffer, base, base, flip, depth, output) else {
+        return false;
+    };
+    print(first, &matched.printable, output);
+    let mut ends = vec![matched.end];
+    let mut got_match = vec![false, false];
+    let mut level = 1;
+    for rule in &rules[1..] {
+        let rule_level = rule.level.saturating_sub(first.level) as usize;
+        if rule_level > level || rule_level == 0 {
+            continue;
+        }
+        level = rule_level;
+        let previous_end = ends[level - 1];
+        let Some(matched) = test(magic, rule, buffer, base, previous_end, flip, depth, output)
+        else {
+            continue;
+        };
+        match rule.kind {
+            Kind::Clear => got_match[level] = false,
+            Kind::Default if got_match[level] => continue,
+            _ => got_match[level] = true,
+        }
+        print(rule, &matched.printable, output);
+        ends.truncate(level);
+        ends.push(matched.end);
+        got_match.truncate(level + 1);
+        got_match.push(false);
+        level += 1;
+    }
+    true
+}
+
+fn print(rule: &Rule, printable: &Printable, output: &mut Output) {
+    if output.mime.is_none() {
+        output.mime = rule.mime.as_deref().map(expand_conditions);
+    }
+    if output.extensions.is_empty() {
+        output.extensions.clone_from(&rule.extensions);
+    }
+    if rule.description.is_empty() {
+        return;
+    }
+    output.found = true;
+    if output.need_separator && !rule.no_space {
+        output.description.push(' ');
+    }
+    output
+        .description
+        .push_str(&format_description(&rule.description, printable));
+    output.need_separator = true;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn test(
+    magic: &Magic,
+    rule: &Rule,
+    buffer: Buffer,
+    base: usize,
+    previous_end: usize,
+    flip: bool,
+    depth: u8,
+    output: &mut Output,
+) -> Option<Matched> {
+    let position = resolve_offset(&rule.offset, buffer, base, previous_end, flip)?;
+    let data = buffer.data;
+    match &rule.kind {
+        Kind::Numeric { number, signed } => {
+            test_number(rule, *number, *signed, data, position, flip)
+        }
+        Kind::String { flags } => {
+            let rest = data.get(position..)?;
+            test_string(rule, rest, *flags).map(|(length, printable)| Matched {
+                end: position + length,
+                printable,
+            })
+        }
+        Kind::PString { flags } => {
+            let length_number = StringFlags::pstring_length(*flags);
+            let mut length = length_number.read(data, position, false)? as usize;
+            let width = length_number.width as usize;
+            if flags & StringFlags::PSTRING_INCLUDES_LENGTH != 0 {
+                length = length.checked_sub(width)?;
+            }
+            let start = position + width;
+            let rest = data.get(start..start.checked_add(length)?.min(data.len()))?;
+            test_string(rule, rest, *flags).map(|(length, printable)| Matched {
+                end: start + length,
+                printable,
+            })
+        }
+        Kind::String16 { endian } => {
+            let rest = data.get(position..)?;
+            let endian = if flip { endian.flip() } else { *endian };
+            let narrowed: Vec<u8> = rest
+                .chunks_exact(2)
+                .map_while(|pair| {
+                    let (high, low) = match endian {
+                        Endian::Big => (pair[0], pair[1]),
+                        _ => (pair[1], pair[0]),
+                    };
+                    (high == 0).then_some(low)
+                })
+                .collect();
+            test_string(rule, &narrowed, 0).map(|(length, printable)| Matched {
+                end: position + length * 2,
+                printable,
+            })
+        }
+        Kind::Search { range, flags } => test_search(rule, data, position, *range, *flags),
+        Kind::Default | Kind::Clear | Kind::Name(_) => Some(Matched {
+            end: position,
+            printable: Printable::Text(Vec::new()),
+        }),
+        Kind::Use {
+            name,
+            flip: flip_use,
+        } => {
+            let named = magic.named.get(name)?;
+            if depth >= MAX_USE_DEPTH {
+                return None;
+            }
+            // A subroutine only counts as matching when something in it
+            // produced a description, which lets silent "checker" routines
+            // guard the rules nested under the `use`.
+            let outer_separator = output.need_separator;
+            let outer_found = std::mem::replace(&mut output.found, false);
+            if rule.no_space {
+                output.need_separator = false;
+            }
+            evaluate(
+                magic,
+                named,
+                buffer,
+                position,
+                flip ^ flip_use,
+                depth + 1,
+                output,
+            );
+            let found = std::mem::replace(&mut output.found, outer_found) || outer_found;
+            if !found {
+                output.need_separator = outer_separator;
+                return None;
+            }
+            Some(Matched {
+                end: position,
+                printable: Printable::Text(Vec::new()),
+            })
+        }
+        Kind::Unsupported => None,
+    }
+}
+
+fn resolve_offset(
+    offset: &Offset,
+    buffer: Buffer,
+    base: usize,
+    previous_end: usize,
+    flip: bool,
+) -> Option<usize> {
+    let data = buffer.data;
+    let mut position = if offset.from_end {
+        if !buffer.complete {
+            return None;
+        }
+        (data.len() as i64).checked_add(offset.value)?
+    } else if offset.relative {
+        (previous_end as i64).checked_add(offset.value)?
+    } else {
+        (base as i64).checked_add(offset.value)?
+    };
+    if let Some(indirect) = offset.indirect {
+        let read = |at: i64| -> Option<i64> {
+            let raw = indirect
+                .number
+                .read(data, usize::try_from(at).ok()?, flip)?;
+            Some(match indirect.number.format {
+                NumberFormat::Float if indirect.number.width == 8 => f64::from_bits(raw) as i64,
+                NumberFormat::Float => f32::from_bits(raw as u32) as i64,
+                _ if indirect.signed => sign_extend(raw, indirect.number.width) as i64,
+                _ => raw as i64,
+            })
+        };
+        let mut operand = indirect.operand;
+        if indirect.operand_indirect {
+            operand = read(position.checked_add(operand)?)?;
+        }
+        let value = read(position)?;
+        let mut result = match indirect.operator {
+            Some(operator) if operand != 0 => operator.apply(value as u64, operand as u64)? as i64,
+            _ => value,
+        };
+        if indirect.inverse {
+            result = !result;
+        }
+        position = (base as i64).checked_add(result)?;
+        if indirect.relative_result {
+            position = position.checked_add(previous_end as i64)?;
+        }
+    }
+    usize::try_from(position).ok()
+}
+
+const fn width_mask(width: u8) -> u64 {
+    if width >= 8 {
+        u64::MAX
+    } else {
+        (1 << (width as u32 * 8)) - 1
+    }
+}
+
+const fn sign_extend(value: u64, width: u8) -> u64 {
+    if width >= 8 {
+        return value;
+    }
+    let shift = 64 - width as u32 * 8;
+    (((value << shift) as i64) >> shift) as u64
+}
+
+fn test_number(
+    rule: &Rule,
+    number: Number,
+    signed: bool,
+    data: &[u8],
+    position: usize,
+    flip: bool,
+) -> Option<Matched> {
+    let raw = number.read(data, position, flip)?;
+    let end = position + number.width as usize;
+    if number.format == NumberFormat::Float {
+        let value = if number.width == 8 {
+            f64::from_bits(raw)
+        } else {
+            f32::from_bits(raw as u32) as f64
+        };
+        let expected = match rule.value {
+            Value::Float(expected) => expected,
+            Value::Number(expected) => expected as f64,
+            Value::Bytes(_) => return None,
+        };
+        let matched = match rule.relation {
+            Relation::Any => true,
+            Relation::Equal => value == expected,
+            Relation::NotEqual => value != expected,
+            Relation::Less => value < expected,
+            Relation::Greater => value > expected,
+            Relation::AllSet | Relation::AnyClear => false,
+        };
+        return matched.then_some(Matched {
+            end,
+            printable: Printable::Float(value),
+        });
+    }
+    let mask = width_mask(number.width);
+    let mut value = raw;
+    if let Some((operator, operand)) = rule.mask.filter(|&(_, operand)| operand != 0) {
+        value = operator.apply(value, operand)? & mask;
+    }
+    if rule.inverse {
+        value = !value & mask;
+    }
+    let expected = match rule.value {
+        Value::Number(expected) => expected & mask,
+        _ => 0,
+    };
+    let (left, right) = if signed {
+        (
+            sign_extend(value, number.width),
+            sign_extend(expected, number.width),
+        )
+    } else {
+        (value, expected)
+    };
+    let matched = match rule.relation {
+        Relation::Any => true,
+        Relation::Equal => left == right,
+        Relation::NotEqual => left != right,
+        Relation::Less if signed => (left as i64) < (right as i64),
+        Relation::Less => left < right,
+        Relation::Greater if signed => (left as i64) > (right as i64),
+        Relation::Greater => left > right,
+        Relation::AllSet => left & right == right,
+        Relation::AnyClear => left & right != right,
+    };
+    if !matched {
+        return None;
+    }
+    let printable = match number.format {
+        NumberFormat::Date | NumberFormat::LocalDate => {
+            Printable::Text(format_date(sign_extend(value, number.width) as i64).into_bytes())
+        }
+        NumberFormat::WindowsDate => {
+            Printable::Text(format_date((value / 10_000_000) as i64 - 11_644_473_600).into_bytes())
+        }
+        _ => Printable::Integer {
+            value,
+            width: number.width,
+        },
+    };
+    Some(Matched { end, printable })
+}
+
+const fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | 0x0B | 0x0C | b'\r')
+}
+
+/// Compares `pattern` against the start of `data` with libmagic's string
+/// modifiers, returning the sign of the first difference (data minus
+/// pattern) and how many bytes of `data` took part in the comparison.
+fn compare_strings(pattern: &[u8], data: &[u8], flags: u32) -> (i32, usize) {
+    let at = |index: usize| data.get(index).copied().unwrap_or(0);
+    let mut data_index = 0;
+    let mut pattern_index = 0;
+    let mut difference = 0;
+    while pattern_index < pattern.len() {
+        let expected = pattern[pattern_index];
+        pattern_index += 1;
+        if flags & StringFlags::IGNORE_LOWERCASE != 0 && expected.is_ascii_lowercase() {
+            difference = at(data_index).to_ascii_lowercase() as i32 - expected as i32;
+            data_index += 1;
+        } else if flags & StringFlags::IGNORE_UPPERCASE != 0 && expected.is_ascii_uppercase() {
+            difference = at(data_index).to_ascii_uppercase() as i32 - expected as i32;
+            data_index += 1;
+        } else if flags & StringFlags::COMPACT_WHITESPACE != 0 && is_space(expected) {
+            if !is_space(at(data_index)) {
+                difference = 1;
+                break;
+            }
+            data_index += 1;
+            if !pattern.get(pattern_index).copied().is_some_and(is_space) {
+                while data_index < data.len() && is_space(data[data_index]) {
+                    data_index += 1;
+                }
+            }
+        } else if flags & StringFlags::OPTIONAL_WHITESPACE != 0 && is_space(expected) {
+            while data_index < data.len() && is_space(data[data_index]) {
+                data_index += 1;
+            }
+        } else {
+            difference = at(data_index) as i32 - expected as i32;
+            data_index += 1;
+        }
+        if difference != 0 {
+            break;
+        }
+    }
+    if difference == 0
+        && flags & StringFlags::FULL_WORD != 0
+        && data_index < data.len()
+        && !is_space(data[data_index])
+    {
+        difference = 1;
+    }
+    (difference, data_index)
+}
+
+/// The string libmagic prints for `%s` when a string test matched anything
+/// other than its own pattern: the data up to a NUL, or up to the end of the
+/// line when the pattern was empty.
+fn data_string(data: &[u8], pattern: &[u8], flags: u32) -> Vec<u8> {
+    let limit = data.len().min(MAX_STRING_LENGTH);
+    let mut end = data[..limit]
+        .iter()
+        .position(|&byte| {
+            byte == 0
+                || (pattern.first().is_none_or(|&first| first == 0)
+                    && matches!(byte, b'\r' | b'\n'))
+        })
+        .unwrap_or(limit);
+    let mut start = 0;
+    if flags & StringFlags::TRIM != 0 {
+        while start < end && is_space(data[start]) {
+            start += 1;
+        }
+        while end > start && is_space(data[end - 1]) {
+            end -= 1;
+        }
+    }
+    data[start..end].to_vec()
+}
+
+fn test_string(rule: &Rule, data: &[u8], flags: u32) -> Option<(usize, Printable)> {
+    let Value::Bytes(pattern) = &rule.value else {
+        return None;
+    };
+    let (difference, length) = if rule.relation == Relation::Any {
+        (0, 0)
+    } else {
+        compare_strings(pattern, data, flags)
+    };
+    let matched = match rule.relation {
+        Relation::Any => true,
+        Relation::Equal => difference == 0,
+        Relation::NotEqual => difference != 0,
+        Relation::Less => difference < 0,
+        Relation::Greater => difference > 0,
+        Relation::AllSet | Relation::AnyClear => false,
+    };
+    if !matched {
+        return None;
+    }
+    if matches!(rule.relation, Relation::Equal | Relation::NotEqual) {
+        return Some((length, Printable::Text(pattern.clone())));
+    }
+    let printed = data_string(data, pattern, flags);
+    Some((printed.len(), Printable::Text(printed)))
+}
+
+fn test_search(
+    rule: &Rule,
+    data: &[u8],
+    position: usize,
+    range: u32,
+    flags: u32,
+) -> Option<Matched> {
+    let Value::Bytes(pattern) = &rule.value else {
+        return None;
+    };
+    let window = data.get(position..)?;
+    let limit = if range == 0 {
+        window.len()
+    } else {
+        window.len().min(range as usize)
+    };
+    let found = if pattern.is_empty() {
+        Some((0, 0))
+    } else if flags
+        & (StringFlags::IGNORE_LOWERCASE
+            | StringFlags::IGNORE_UPPERCASE
+            | StringFlags::COMPACT_WHITESPACE
+            | StringFlags::OPTIONAL_WHITESPACE
+            | StringFlags::FULL_WORD)
+        == 0
+    {
+        let end = (limit + pattern.len() - 1).min(window.len());
+        window[..end]
+            .windows(pattern.len())
+            .position(|candidate| candidate == pattern.as_slice())
+            .map(|index| (index, pattern.len()))
+    } else {
+        (0..limit).find_map(
+            |index| match compare_strings(pattern, &window[index..], flags) {
+                (0, length) => Some((index, length)),
+                _ => None,
+            },
+        )
+    };
+    let matched = match rule.relation {
+        Relation::Any => true,
+        Relation::Equal => found.is_some(),
+        Relation::NotEqual => found.is_none(),
+        _ => false,
+    };
+    if !matched {
+        return None;
+    }
+    let (index, length) = found.unwrap_or((0, 0));
+    let end = if flags & StringFlags::OFFSET_START != 0 {
+        position + index
+    } else {
+        position + index + length
+    };
+    Some(Matched {
+        end,
+        printable: Printable::Text(pattern.clone()),
+    })
+}
+
+/// Formats a description the way libmagic passes it to `printf`, with the
+/// value the rule read as the single argument.
+fn format_description(description: &str, printable: &Printable) -> String {
+    let description = expand_conditions(description);
+    let mut output = String::with_capacity(description.len());
+    let mut chars = description.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            output.push('%');
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            spec.width = spec.width * 10 + digit as usize;
+            chars.next();
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut precision = 0;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                precision = precision * 10 + digit as usize;
+                chars.next();
+            }
+            spec.precision = Some(precision);
+        }
+        while chars
+            .peek()
+            .is_some_and(|c| matches!(c, 'h' | 'l' | 'L' | 'q' | 'j' | 'z' | 't'))
+        {
+            chars.next();
+        }
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        output.push_str(&spec.format(conversion, printable));
+    }
+    output
+}
+
+/// Expands libmagic's `${x?when executable:otherwise}` in descriptions and
+/// MIME types. The file mode is not known from the bytes alone, so the second
+/// branch is used.
+fn expand_conditions(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&format_condition(&rest[start + 2..start + end]));
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn format_condition(condition: &str) -> String {
+    match condition
+        .strip_prefix("x?")
+        .and_then(|branches| branches.split_once(':'))
+    {
+        Some((_, otherwise)) => otherwise.to_owned(),
+        None => format!("${{{condition}}}"),
+    }
+}
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    alternate: bool,
+    plus: bool,
+    space: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn format(&self, conversion: char, printable: &Printable) -> String {
+        let (body, numeric) = match (conversion, printable) {
+            ('s', Printable::Text(text)) => {
+                let mut text = printable_string(text);
+                if let Some(precision) = self.precision {
+                    text = text.chars().take(precision).collect();
+                }
+                (text, false)
+            }
+            ('c', Printable::Integer { value, .. }) => {
+                (((*value as u8) as char).to_string(), false)
+            }
+            ('c', Printable::Text(text)) => (
+                text.first()
+                    .map(|&byte| (byte as char).to_string())
+                    .unwrap_or_default(),
+                false,
+            ),
+            ('e' | 'E' | 'f' | 'F' | 'g' | 'G', Printable::Float(value)) => {
+                let precision = self.precision.unwrap_or(6);
+                let body = match conversion {
+                    'e' | 'E' => format!("{value:.precision$e}"),
+                    'g' | 'G' => format!("{value}"),
+                    _ => format!("{value:.precision$}"),
+                };
+                (self.sign(body, *value < 0.0), true)
+            }
+            (_, Printable::Float(value)) => (format!("{value}"), true),
+            (_, Printable::Integer { value, width }) => {
+                (self.integer(conversion, *value, *width), true)
+            }
+            (_, Printable::Text(text)) => (printable_string(text), false),
+        };
+        self.pad(body, numeric)
+    }
+
+    fn sign(&self, body: String, negative: bool) -> String {
+        if negative {
+            body
+        } else if self.plus {
+            format!("+{body}")
+        } else if self.space {
+            format!(" {body}")
+        } else {
+            body
+        }
+    }
+
+    fn integer(&self, conversion: char, value: u64, width: u8) -> String {
+        // printf sees at least an `int`, so narrow values are promoted unsigned
+        // and 32-bit values are reinterpreted at that width.
+        let value = value & width_mask(width);
+        let digits = match conversion {
+            'd' | 'i' => {
+                let signed = if width >= 8 {
+                    value as i64
+                } else if width == 4 {
+                    value as u32 as i32 as i64
+                } else {
+                    value as i64
+                };
+                let digits = self.minimum_digits(signed.unsigned_abs().to_string());
+                return self.sign(
+                    if signed < 0 {
+                        format!("-{digits}")
+                    } else {
+                        digits
+                    },
+                    signed < 0,
+                );
+            }
+            'x' => self.minimum_digits(format!("{value:x}")),
+            'X' => self.minimum_digits(format!("{value:X}")),
+            'o' => self.minimum_digits(format!("{value:o}")),
+            _ => self.minimum_digits(value.to_string()),
+        };
+        match conversion {
+            'x' if self.alternate && value != 0 => format!("0x{digits}"),
+            'X' if self.alternate && value != 0 => format!("0X{digits}"),
+            'o' if self.alternate && !digits.starts_with('0') => format!("0{digits}"),
+            _ => digits,
+        }
+    }
+
+    fn minimum_digits(&self, digits: String) -> String {
+        match self.precision {
+            Some(precision) if digits.len() < precision => {
+                format!("{}{digits}", "0".repeat(precision - digits.len()))
+            }
+            _ => digits,
+        }
+    }
+
+    fn pad(&self, body: String, numeric: bool) -> String {
+        let length = body.chars().count();
+        if length >= self.width {
+            return body;
+        }
+        let padding = self.width - length;
+        if self.left {
+            format!("{body}{}", " ".repeat(padding))
+        } else if self.zero && numeric && self.precision.is_none() {
+            let split = if body.starts_with("0x") || body.starts_with("0X") {
+                2
+            } else {
+                usize::from(body.starts_with(['-', '+', ' ']))
+            };
+            format!(
+                "{}{}{}",
+                &body[..split],
+                "0".repeat(padding),
+                &body[split..]
+            )
+        } else {
+            format!("{}{body}", " ".repeat(padding))
+        }
+    }
+}
+
+/// Escapes unprintable bytes as octal the way libmagic's `file_printable`
+/// does.
+fn printable_string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_owned(),
+        _ => bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    (byte as char).to_string()
+                } else {
+                    format!("\\{byte:03o}")
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Formats seconds since the Unix epoch like `ctime(3)` in UTC.
+pub(crate) fn format_date(seconds: i64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{} {} {:2} {:02}:{:02}:{:02} {}",
+        DAYS[days.rem_euclid(7) as usize],
+        MONTHS[(month - 1) as usize],
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        year
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_description() {
+        let integer = Printable::Integer {
+            value: 0x2a,
+            width: 2,
+        };
+        assert_eq!(format_description("version %d", &integer), "version 42");
+        assert_eq!(format_description("[%#06x]", &integer), "[0x002a]");
+        assert_eq!(format_description("%-4u|", &integer), "42  |");
+        let negative = Printable::Integer {
+            value: 0xFFFF_FFFF,
+            width: 4,
+        };
+        assert_eq!(format_description("%d", &negative), "-1");
+        let text = Printable::Text(b"ab\x01".to_vec());
+        assert_eq!(
+            format_description("\"%s\" 100%%", &text),
+            "\"ab\\001\" 100%"
+        );
+    }
+
+    #[test]
+    fn test_resolve_offset_overflow() {
+        let data = i64::MAX.to_le_bytes();
+        let buffer = Buffer {
+            data: &data,
+            complete: true,
+        };
+        let relative = Offset {
+            value: i64::MAX,
+            relative: true,
+            from_end: false,
+            indirect: None,
+        };
+        assert_eq!(resolve_offset(&relative, buffer, 0, 8, false), None);
+        let indirect = Offset {
+            value: 0,
+            relative: false,
+            from_end: false,
+            indirect: Some(Indirect {
+                number: Number::new(8, Endian::Little, NumberFormat::Integer),
+                signed: true,
+                operator: None,
+                operand: 0,
+                operand_indirect: false,
+                inverse: false,
+                relative_result: true,
+            }),
+        };
+        assert_eq!(resolve_offset(&indirect, buffer, 1, 8, false), None);
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(format_date(1_700_000_000), "Tue Nov 14 22:13:20 2023");
+        assert_eq!(format_date(951_827_696), "Tue Feb 29 12:34:56 2000");
+    }
+
+    #[test]
+    fn test_compare_strings() {
+        use StringFlags as F;
+        assert_eq!(compare_strings(b"abc", b"abcd", 0), (0, 3));
+        assert_eq!(compare_strings(b"abc", b"ABC", F::IGNORE_LOWERCASE).0, 0);
+        assert_eq!(
+            compare_strings(b"a b", b"a    b", F::COMPACT_WHITESPACE),
+            (0, 6)
+        );
+        assert_ne!(compare_strings(b"ab", b"a b", F::OPTIONAL_WHITESPACE).0, 0);
+        assert_eq!(compare_strings(b"a b", b"ab", F::OPTIONAL_WHITESPACE).0, 0);
+        assert_ne!(compare_strings(b"int", b"integer", F::FULL_WORD).0, 0);
+    }
+}
@@ -0,0 +1,675 @@
+use crate::magic::rule::*;
+
+struct Cursor<'a> {
+    line: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    const fn new(line: &'a [u8]) -> Self {
+        Self { line, position: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.line.get(self.position).copied()
+    }
+
+    fn peek_at(&self, distance: usize) -> Option<u8> {
+        self.line.get(self.position + distance).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        self.position != start
+    }
+
+    fn token(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        &self.line[start..self.position]
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.line[self.position.min(self.line.len())..]
+    }
+
+    /// Parses an integer the way `strtoull(_, _, 0)` does: an optional sign,
+    /// then hexadecimal with `0x`, octal with a leading `0`, or decimal.
+    fn integer(&mut self) -> Option<u64> {
+        let start = self.position;
+        let negative = if self.eat(b'-') {
+            true
+        } else {
+            self.eat(b'+');
+            false
+        };
+        let radix = if self.peek() == Some(b'0')
+            && matches!(self.peek_at(1), Some(b'x' | b'X'))
+            && self.peek_at(2).is_some_and(|byte| byte.is_ascii_hexdigit())
+        {
+            self.position += 2;
+            16
+        } else if self.peek() == Some(b'0') {
+            8
+        } else {
+            10
+        };
+        let digits_start = self.position;
+        let mut value: u64 = 0;
+        while let Some(digit) = self.peek().and_then(|byte| (byte as char).to_digit(radix)) {
+            value = value.wrapping_mul(radix as u64).wrapping_add(digit as u64);
+            self.position += 1;
+        }
+        if self.position == digits_start {
+            self.position = start;
+            return None;
+        }
+        Some(if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        })
+    }
+
+    fn float(&mut self) -> Option<f64> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'-' | b'+'))
+        {
+            self.position += 1;
+        }
+        let parsed = std::str::from_utf8(&self.line[start..self.position])
+            .ok()
+            .and_then(|text| text.parse().ok());
+        if parsed.is_none() {
+            self.position = start;
+        }
+        parsed
+    }
+
+    /// Reads a test value up to the next unescaped whitespace, decoding the C
+    /// style escapes magic(5) allows.
+    fn string(&mut self) -> Vec<u8> {
+        let mut value = Vec::new();
+        while let Some(byte) = self.peek() {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            self.position += 1;
+            if byte != b'\\' {
+                value.push(byte);
+                continue;
+            }
+            let Some(escaped) = self.bump() else {
+                value.push(b'\\');
+                break;
+            };
+            let decoded = match escaped {
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'f' => 0x0C,
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'v' => 0x0B,
+                b'0'..=b'7' => {
+                    let mut code = (escaped - b'0') as u32;
+                    for _ in 0..2 {
+                        match self.peek() {
+                            Some(digit @ b'0'..=b'7') => {
+                                code = code * 8 + (digit - b'0') as u32;
+                                self.position += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    code as u8
+                }
+                b'x' => {
+                    let mut code = None;
+                    for _ in 0..2 {
+                        match self.peek().and_then(|byte| (byte as char).to_digit(16)) {
+                            Some(digit) => {
+                                code = Some(code.unwrap_or(0) * 16 + digit as u8);
+                                self.position += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                    code.unwrap_or(b'x')
+                }
+                other => other,
+            };
+            value.push(decoded);
+        }
+        value
+    }
+}
+
+enum Line {
+    Rule(Box<Rule>),
+    Mime(String),
+    Extensions(Vec<String>),
+    Strength(StrengthOp, u8),
+    Ignored,
+}
+
+/// Parses magic(5) source into a flat list of rules in file order, along
+/// with a warning for every line that could not be parsed. Like GNU file, a
+/// bad line is skipped together with the lines that continue it, and the
+/// rest of the source still loads. Types this crate cannot evaluate are kept
+/// as [`Kind::Unsupported`] so the level structure of the surrounding rules
+/// stays intact.
+pub(crate) fn parse(source: &[u8]) -> (Vec<Rule>, Vec<String>) {
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut warnings = Vec::new();
+    // the level of the last rule skipped, whose continuations go with it
+    let mut skipped = None;
+    for (index, line) in source.split(|&byte| byte == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut warn =
+            |message: &str| warnings.push(format!("magic line {}: {message}", index + 1));
+        let parsed = match parse_line(line) {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                warn(&message);
+                if let Some(level) = rule_level(line) {
+                    skipped = Some(skipped.map_or(level, |skipped: u16| skipped.min(level)));
+                }
+                continue;
+            }
+        };
+        match parsed {
+            Line::Rule(rule) => match skipped {
+                Some(level) if rule.level > level => {}
+                _ => {
+                    skipped = None;
+                    rules.push(*rule);
+                }
+            },
+            Line::Ignored => {}
+            // annotations of a skipped rule go with it
+            _ if skipped.is_some() => {}
+            annotation => {
+                let Some(rule) = rules.last_mut() else {
+                    warn("annotation without a rule");
+                    continue;
+                };
+                match annotation {
+                    Line::Mime(mime) => rule.mime = Some(mime),
+                    Line::Extensions(extensions) => rule.extensions = extensions,
+                    Line::Strength(op, factor) => rule.strength = Some((op, factor)),
+                    Line::Rule(_) | Line::Ignored => unreachable!(),
+                }
+            }
+        }
+    }
+    (rules, warnings)
+}
+
+/// The continuation level of a line that is meant to be a rule, or `None`
+/// for comments and annotations.
+fn rule_level(line: &[u8]) -> Option<u16> {
+    let mut cursor = Cursor::new(line);
+    cursor.skip_whitespace();
+    match cursor.peek() {
+        None | Some(b'#') => None,
+        Some(b'!') if cursor.peek_at(1) == Some(b':') => None,
+        _ => {
+            let mut level = 0;
+            while cursor.eat(b'>') {
+                level += 1;
+            }
+            Some(level)
+        }
+    }
+}
+
+fn parse_line(line: &[u8]) -> Result<Line, String> {
+    let mut cursor = Cursor::new(line);
+    cursor.skip_whitespace();
+    match cursor.peek() {
+        None | Some(b'#') => return Ok(Line::Ignored),
+        Some(b'!') if cursor.peek_at(1) == Some(b':') => {
+            cursor.position += 2;
+            return parse_annotation(&mut cursor);
+        }
+        _ => {}
+    }
+    let mut level = 0;
+    while cursor.eat(b'>') {
+        level += 1;
+    }
+    let offset = parse_offset(&mut cursor)?;
+    if !cursor.skip_whitespace() {
+        return Err("expected whitespace after offset".to_owned());
+    }
+    let type_start = cursor.position;
+    while cursor
+        .peek()
+        .is_some_and(|byte| byte.is_ascii_alphanumeric())
+    {
+        cursor.position += 1;
+    }
+    let type_name = &line[type_start..cursor.position];
+    let (mut kind, unsigned) = parse_type(type_name)
+        .ok_or_else(|| format!("unknown type `{}`", String::from_utf8_lossy(type_name)))?;
+    let mut inverse = false;
+    if cursor.eat(b'~') {
+        inverse = !kind.is_string();
+    }
+    let mut mask = None;
+    if let Some(op) = cursor.peek().and_then(Operator::from_char) {
+        cursor.position += 1;
+        if kind.is_string() || matches!(kind, Kind::Unsupported) {
+            if op != Operator::Divide {
+                return Err("string types only take `/` modifiers".to_owned());
+            }
+            parse_string_modifiers(&mut cursor, &mut kind)?;
+        } else {
+            let operand = cursor
+                .integer()
+                .ok_or_else(|| "expected a number after the mask operator".to_owned())?;
+            mask = Some((op, operand));
+        }
+    }
+    if let Kind::Numeric { signed, .. } = &mut kind {
+        *signed = !unsigned;
+    }
+    cursor.skip_whitespace();
+    let relation = match cursor.peek() {
+        Some(b'=') => Relation::Equal,
+        Some(b'!') => Relation::NotEqual,
+        Some(b'<') => Relation::Less,
+        Some(b'>') => Relation::Greater,
+        Some(b'&') => Relation::AllSet,
+        Some(b'^') => Relation::AnyClear,
+        Some(b'x')
+            if cursor
+                .peek_at(1)
+                .is_none_or(|byte| byte.is_ascii_whitespace()) =>
+        {
+            Relation::Any
+        }
+        _ => Relation::Equal,
+    };
+    if relation != Relation::Equal || cursor.peek() == Some(b'=') {
+        cursor.position += 1;
+        if matches!(
+            relation,
+            Relation::AllSet | Relation::AnyClear | Relation::Equal
+        ) {
+            cursor.eat(b'=');
+        }
+    }
+    let value = if relation == Relation::Any {
+        Value::Number(0)
+    } else {
+        parse_value(&mut cursor, &mut kind)?
+    };
+    cursor.skip_whitespace();
+    let mut description = cursor.rest();
+    let mut no_space = false;
+    if let Some(stripped) = description.strip_prefix(b"\x08") {
+        description = stripped;
+        no_space = true;
+    } else if let Some(stripped) = description.strip_prefix(b"\\b") {
+        description = stripped;
+        no_space = true;
+    }
+    let description = String::from_utf8_lossy(description).trim_end().to_owned();
+    Ok(Line::Rule(Box::new(Rule {
+        level,
+        offset,
+        kind,
+        mask,
+        inverse,
+        relation,
+        value,
+        description,
+        no_space,
+        mime: None,
+        extensions: Vec::new(),
+        strength: None,
+    })))
+}
+
+fn parse_annotation(cursor: &mut Cursor) -> Result<Line, String> {
+    let key = cursor.token();
+    cursor.skip_whitespace();
+    match key {
+        b"mime" => Ok(Line::Mime(
+            String::from_utf8_lossy(cursor.token()).into_owned(),
+        )),
+        b"ext" => Ok(Line::Extensions(
+            String::from_utf8_lossy(cursor.token())
+                .split('/')
+                .filter(|extension| !extension.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        )),
+        b"strength" => {
+            let op = match cursor.bump() {
+                Some(b'+') => StrengthOp::Add,
+                Some(b'-') => StrengthOp::Subtract,
+                Some(b'*') => StrengthOp::Multiply,
+                Some(b'/') => StrengthOp::Divide,
+                _ => return Err("expected one of `+-*/` after !:strength".to_owned()),
+            };
+            cursor.skip_whitespace();
+            let factor = cursor
+                .integer()
+                .and_then(|factor| u8::try_from(factor).ok())
+                .ok_or_else(|| "!:strength factor must be between 0 and 255".to_owned())?;
+            Ok(Line::Strength(op, factor))
+        }
+        _ => Ok(Line::Ignored),
+    }
+}
+
+fn parse_offset(cursor: &mut Cursor) -> Result<Offset, String> {
+    let relative = cursor.eat(b'&');
+    if !cursor.eat(b'(') {
+        let from_end = !relative && cursor.peek() == Some(b'-');
+        let value = cursor
+            .integer()
+            .ok_or_else(|| "expected an offset".to_owned())?;
+        return Ok(Offset {
+            value: value as i64,
+            relative,
+            from_end,
+            indirect: None,
+        });
+    }
+    let inner_relative = cursor.eat(b'&');
+    let from_end = !inner_relative && cursor.peek() == Some(b'-');
+    let value = cursor
+        .integer()
+        .ok_or_else(|| "expected an indirect offset".to_owned())?;
+    let mut number = Number::new(4, Endian::Native, NumberFormat::Integer);
+    let mut signed = false;
+    if matches!(cursor.peek(), Some(b'.' | b',')) {
+        signed = cursor.bump() == Some(b',');
+        number = match cursor.bump() {
+            Some(b'b' | b'B' | b'c' | b'C') => {
+                Number::new(1, Endian::Little, NumberFormat::Integer)
+            }
+            Some(b'h' | b's') => Number::new(2, Endian::Little, NumberFormat::Integer),
+            Some(b'H' | b'S') => Number::new(2, Endian::Big, NumberFormat::Integer),
+            Some(b'l') => Number::new(4, Endian::Little, NumberFormat::Integer),
+            Some(b'L') => Number::new(4, Endian::Big, NumberFormat::Integer),
+            Some(b'm') => Number::new(4, Endian::Middle, NumberFormat::Integer),
+            Some(b'q') => Number::new(8, Endian::Little, NumberFormat::Integer),
+            Some(b'Q') => Number::new(8, Endian::Big, NumberFormat::Integer),
+            Some(b'i') => Number::new(4, Endian::Little, NumberFormat::Id3),
+            Some(b'I') => Number::new(4, Endian::Big, NumberFormat::Id3),
+            Some(b'e' | b'f' | b'g') => Number::new(8, Endian::Little, NumberFormat::Float),
+            Some(b'E' | b'F' | b'G') => Number::new(8, Endian::Big, NumberFormat::Float),
+            _ => return Err("unknown indirect offset type".to_owned()),
+        };
+    }
+    let inverse = cursor.eat(b'~');
+    let mut operator = None;
+    let mut operand = 0;
+    let mut operand_indirect = false;
+    if let Some(op) = cursor.peek().and_then(Operator::from_char) {
+        cursor.position += 1;
+        operator = Some(op);
+        operand_indirect = cursor.eat(b'(');
+        operand = cursor
+            .integer()
+            .ok_or_else(|| "expected an indirect offset operand".to_owned())?
+            as i64;
+        if operand_indirect && !cursor.eat(b')') {
+            return Err("unterminated indirect operand".to_owned());
+        }
+    }
+    if !cursor.eat(b')') {
+        return Err("unterminated indirect offset".to_owned());
+    }
+    Ok(Offset {
+        value: value as i64,
+        relative: inner_relative,
+        from_end,
+        indirect: Some(Indirect {
+            number,
+            signed,
+            operator,
+            operand,
+            operand_indirect,
+            inverse,
+            relative_result: relative,
+        }),
+    })
+}
+
+fn parse_type(name: &[u8]) -> Option<(Kind, bool)> {
+    let numeric = |width, endian, format| Kind::Numeric {
+        number: Number::new(width, endian, format),
+        signed: true,
+    };
+    if let Some(rest) = name.strip_prefix(b"u") {
+        if let Some((kind @ Kind::Numeric { .. }, _)) = parse_type(rest) {
+            return Some((kind, true));
+        }
+    }
+    let (endian, base) = if let Some(rest) = name.strip_prefix(b"be") {
+        (Endian::Big, rest)
+    } else if let Some(rest) = name.strip_prefix(b"le") {
+        (Endian::Little, rest)
+    } else if let Some(rest) = name.strip_prefix(b"me") {
+        (Endian::Middle, rest)
+    } else {
+        (Endian::Native, name)
+    };
+    let kind = match (endian, base) {
+        (Endian::Native, b"byte") => numeric(1, endian, NumberFormat::Integer),
+        (Endian::Middle, _) => match base {
+            b"long" => numeric(4, endian, NumberFormat::Integer),
+            b"date" => numeric(4, endian, NumberFormat::Date),
+            b"ldate" => numeric(4, endian, NumberFormat::LocalDate),
+            _ => return None,
+        },
+        (_, b"short") => numeric(2, endian, NumberFormat::Integer),
+        (_, b"long") => numeric(4, endian, NumberFormat::Integer),
+        (_, b"quad") => numeric(8, endian, NumberFormat::Integer),
+        (_, b"float") => numeric(4, endian, NumberFormat::Float),
+        (_, b"double") => numeric(8, endian, NumberFormat::Float),
+        (_, b"date") => numeric(4, endian, NumberFormat::Date),
+        (_, b"ldate") => numeric(4, endian, NumberFormat::LocalDate),
+        (_, b"qdate") => numeric(8, endian, NumberFormat::Date),
+        (_, b"qldate") => numeric(8, endian, NumberFormat::LocalDate),
+        (_, b"qwdate") => numeric(8, endian, NumberFormat::WindowsDate),
+        (Endian::Big | Endian::Little, b"id3") => numeric(4, endian, NumberFormat::Id3),
+        (Endian::Big | Endian::Little, b"string16") => Kind::String16 { endian },
+        (Endian::Native, b"string") => Kind::String { flags: 0 },
+        (Endian::Native, b"pstring") => Kind::PString {
+            flags: StringFlags::PSTRING_1,
+        },
+        (Endian::Native, b"search") => Kind::Search { range: 0, flags: 0 },
+        (Endian::Native, b"default") => Kind::Default,
+        (Endian::Native, b"clear") => Kind::Clear,
+        (Endian::Native, b"name") => Kind::Name(String::new()),
+        (Endian::Native, b"use") => Kind::Use {
+            name: String::new(),
+            flip: false,
+        },
+        (Endian::Native, b"regex" | b"indirect" | b"der" | b"guid" | b"offset" | b"octal")
+        | (Endian::Big | Endian::Little, b"varint" | b"msdosdate" | b"msdostime")
+        | (Endian::Native, b"msdosdate" | b"msdostime") => Kind::Unsupported,
+        _ => return None,
+    };
+    Some((kind, false))
+}
+
+fn parse_string_modifiers(cursor: &mut Cursor, kind: &mut Kind) -> Result<(), String> {
+    loop {
+        if let Some(count) = cursor
+            .peek()
+            .filter(u8::is_ascii_digit)
+            .and(cursor.integer())
+        {
+            if let Kind::Search { range, .. } = kind {
+                *range = u32::try_from(count).map_err(|_| "search range too large".to_owned())?;
+            }
+        }
+        while let Some(byte) = cursor.peek().filter(u8::is_ascii_alphabetic) {
+            let flag = match StringFlags::from_char(byte) {
+                Some(flag) => flag,
+                None if matches!(kind, Kind::Unsupported) => 0,
+                None => return Err(format!("unknown string modifier `{}`", byte as char)),
+            };
+            match kind {
+                Kind::String { flags } | Kind::Search { flags, .. } => *flags |= flag,
+                Kind::PString { flags } => {
+                    if flag
+                        & (StringFlags::PSTRING_1
+                            | StringFlags::PSTRING_2_BE
+                            | StringFlags::PSTRING_2_LE
+                            | StringFlags::PSTRING_4_BE
+                            | StringFlags::PSTRING_4_LE)
+                        != 0
+                    {
+                        *flags &= !StringFlags::PSTRING_1;
+                    }
+                    *flags |= flag;
+                }
+                _ => {}
+            }
+            cursor.position += 1;
+        }
+        if !cursor.eat(b'/') {
+            return Ok(());
+        }
+    }
+}
+
+fn parse_value(cursor: &mut Cursor, kind: &mut Kind) -> Result<Value, String> {
+    match kind {
+        Kind::Numeric { number, .. } if number.format == NumberFormat::Float => {
+            cursor.skip_whitespace();
+            cursor
+                .float()
+                .map(Value::Float)
+                .ok_or_else(|| "expected a floating point value".to_owned())
+        }
+        Kind::Numeric { .. } => {
+            cursor.skip_whitespace();
+            cursor
+                .integer()
+                .map(Value::Number)
+                .ok_or_else(|| "expected a numeric value".to_owned())
+        }
+        Kind::Name(name) => {
+            *name = String::from_utf8_lossy(&cursor.string()).into_owned();
+            Ok(Value::Number(0))
+        }
+        Kind::Use { name, flip } => {
+            let value = cursor.string();
+            let value = match value.strip_prefix(b"^") {
+                Some(stripped) => {
+                    *flip = true;
+                    stripped
+                }
+                None => &value,
+            };
+            *name = String::from_utf8_lossy(value).into_owned();
+            Ok(Value::Number(0))
+        }
+        Kind::Default | Kind::Clear => {
+            cursor.token();
+            Ok(Value::Number(0))
+        }
+        _ => Ok(Value::Bytes(cursor.string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let (rules, warnings) = parse(
+            b"# comment\n\
+              0\tstring\t\\x7fELF\tELF\n\
+              !:mime\tapplication/x-executable\n\
+              >4\tbyte\t1\t32-bit\n\
+              >>(0x3c.l+4)\tuleshort&0xff\t<0x10\t\\b, small\n\
+              >&2\tsearch/256/c\thello\tgreeting\n",
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[0].value, Value::Bytes(b"\x7fELF".to_vec()));
+        assert_eq!(rules[0].mime.as_deref(), Some("application/x-executable"));
+        assert_eq!(rules[1].level, 1);
+        assert_eq!(rules[2].relation, Relation::Less);
+        assert_eq!(rules[2].mask, Some((Operator::And, 0xff)));
+        assert!(rules[2].no_space);
+        assert!(matches!(rules[2].kind, Kind::Numeric { signed: false, .. }));
+        let indirect = rules[2].offset.indirect.unwrap();
+        assert_eq!(rules[2].offset.value, 0x3c);
+        assert_eq!(indirect.operator, Some(Operator::Add));
+        assert_eq!(indirect.operand, 4);
+        assert!(rules[3].offset.relative);
+        assert_eq!(
+            rules[3].kind,
+            Kind::Search {
+                range: 256,
+                flags: StringFlags::IGNORE_LOWERCASE
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let warnings = |source: &[u8]| parse(source).1;
+        assert_eq!(
+            warnings(b"0 notatype 1 thing\n"),
+            ["magic line 1: unknown type `notatype`"]
+        );
+        assert_eq!(warnings(b"\n(4.l bad\n").len(), 1);
+        assert!(warnings(b"\n\n(4.l bad\n")[0].starts_with("magic line 3: "));
+        assert_eq!(
+            warnings(b"!:mime text/plain\n"),
+            ["magic line 1: annotation without a rule"]
+        );
+
+        // a bad entry is dropped with its continuations and annotations, and
+        // a bad continuation with the lines below it
+        let (rules, warnings) = parse(
+            b"0 notatype 1 bad\n\
+              >1 byte 2 bad child\n\
+              !:mime application/x-bad\n\
+              0 byte 1 good\n\
+              >1 notatype 2 bad child\n\
+              >>2 byte 3 bad grandchild\n\
+              >1 byte 4 good child\n",
+        );
+        assert_eq!(warnings.len(), 2);
+        let descriptions: Vec<_> = rules.iter().map(|rule| &rule.description[..]).collect();
+        assert_eq!(descriptions, ["good", "good child"]);
+        assert_eq!(rules[0].mime, None);
+    }
+}
@@ -0,0 +1,351 @@
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Endian {
+    Native,
+    Little,
+    Big,
+    Middle,
+}
+
+impl Endian {
+    pub(crate) const fn flip(self) -> Self {
+        match self {
+            Endian::Little => Endian::Big,
+            Endian::Big => Endian::Little,
+            other => other,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum NumberFormat {
+    Integer,
+    Float,
+    Date,
+    LocalDate,
+    WindowsDate,
+    Id3,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Number {
+    pub(crate) width: u8,
+    pub(crate) endian: Endian,
+    pub(crate) format: NumberFormat,
+}
+
+impl Number {
+    pub(crate) const fn new(width: u8, endian: Endian, format: NumberFormat) -> Self {
+        Self {
+            width,
+            endian,
+            format,
+        }
+    }
+
+    pub(crate) fn read(&self, buffer: &[u8], offset: usize, flip: bool) -> Option<u64> {
+        let width = self.width as usize;
+        let bytes = buffer.get(offset..offset.checked_add(width)?)?;
+        let endian = if flip {
+            self.endian.flip()
+        } else {
+            self.endian
+        };
+        if self.format == NumberFormat::Id3 {
+            let mut value = 0;
+            let mut ordered = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if endian == Endian::Little {
+                ordered.reverse();
+            }
+            for byte in ordered {
+                value = (value << 7) | (byte & 0x7F) as u64;
+            }
+            return Some(value);
+        }
+        let value = match endian {
+            Endian::Big => bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64),
+            Endian::Little => bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64),
+            Endian::Native if cfg!(target_endian = "big") => {
+                bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+            }
+            Endian::Native => bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64),
+            Endian::Middle if width == 4 => {
+                ((bytes[1] as u64) << 24)
+                    | ((bytes[0] as u64) << 16)
+                    | ((bytes[3] as u64) << 8)
+                    | bytes[2] as u64
+            }
+            Endian::Middle => return None,
+        };
+        Some(value)
+    }
+}
+
+pub(crate) struct StringFlags;
+
+impl StringFlags {
+    pub(crate) const COMPACT_WHITESPACE: u32 = 1 << 0;
+    pub(crate) const OPTIONAL_WHITESPACE: u32 = 1 << 1;
+    pub(crate) const IGNORE_LOWERCASE: u32 = 1 << 2;
+    pub(crate) const IGNORE_UPPERCASE: u32 = 1 << 3;
+    pub(crate) const OFFSET_START: u32 = 1 << 4;
+    pub(crate) const TEXT: u32 = 1 << 5;
+    pub(crate) const BINARY: u32 = 1 << 6;
+    pub(crate) const PSTRING_1: u32 = 1 << 7;
+    pub(crate) const PSTRING_2_BE: u32 = 1 << 8;
+    pub(crate) const PSTRING_2_LE: u32 = 1 << 9;
+    pub(crate) const PSTRING_4_BE: u32 = 1 << 10;
+    pub(crate) const PSTRING_4_LE: u32 = 1 << 11;
+    pub(crate) const PSTRING_INCLUDES_LENGTH: u32 = 1 << 12;
+    pub(crate) const TRIM: u32 = 1 << 13;
+    pub(crate) const FULL_WORD: u32 = 1 << 14;
+
+    pub(crate) const fn from_char(flag: u8) -> Option<u32> {
+        Some(match flag {
+            b'W' => Self::COMPACT_WHITESPACE,
+            b'w' => Self::OPTIONAL_WHITESPACE,
+            b'c' => Self::IGNORE_LOWERCASE,
+            b'C' => Self::IGNORE_UPPERCASE,
+            b's' => Self::OFFSET_START,
+            b't' => Self::TEXT,
+            b'b' => Self::BINARY,
+            b'B' => Self::PSTRING_1,
+            b'H' => Self::PSTRING_2_BE,
+            b'h' => Self::PSTRING_2_LE,
+            b'L' => Self::PSTRING_4_BE,
+            b'l' => Self::PSTRING_4_LE,
+            b'J' => Self::PSTRING_INCLUDES_LENGTH,
+            b'T' => Self::TRIM,
+            b'f' => Self::FULL_WORD,
+            _ => return None,
+        })
+    }
+
+    pub(crate) const fn pstring_length(flags: u32) -> Number {
+        let (width, endian) = if flags & Self::PSTRING_2_BE != 0 {
+            (2, Endian::Big)
+        } else if flags & Self::PSTRING_2_LE != 0 {
+            (2, Endian::Little)
+        } else if flags & Self::PSTRING_4_BE != 0 {
+            (4, Endian::Big)
+        } else if flags & Self::PSTRING_4_LE != 0 {
+            (4, Endian::Little)
+        } else {
+            (1, Endian::Little)
+        };
+        Number::new(width, endian, NumberFormat::Integer)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Kind {
+    Numeric { number: Number, signed: bool },
+    String { flags: u32 },
+    String16 { endian: Endian },
+    PString { flags: u32 },
+    Search { range: u32, flags: u32 },
+    Default,
+    Clear,
+    Name(String),
+    Use { name: String, flip: bool },
+    Unsupported,
+}
+
+impl Kind {
+    pub(crate) const fn is_string(&self) -> bool {
+        matches!(
+            self,
+            Kind::String { .. }
+                | Kind::String16 { .. }
+                | Kind::PString { .. }
+                | Kind::Search { .. }
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Operator {
+    And,
+    Or,
+    Xor,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl Operator {
+    pub(crate) const fn from_char(op: u8) -> Option<Self> {
+        Some(match op {
+            b'&' => Operator::And,
+            b'|' => Operator::Or,
+            b'^' => Operator::Xor,
+            b'+' => Operator::Add,
+            b'-' => Operator::Subtract,
+            b'*' => Operator::Multiply,
+            b'/' => Operator::Divide,
+            b'%' => Operator::Modulo,
+            _ => return None,
+        })
+    }
+
+    pub(crate) const fn apply(self, left: u64, right: u64) -> Option<u64> {
+        Some(match self {
+            Operator::And => left & right,
+            Operator::Or => left | right,
+            Operator::Xor => left ^ right,
+            Operator::Add => left.wrapping_add(right),
+            Operator::Subtract => left.wrapping_sub(right),
+            Operator::Multiply => left.wrapping_mul(right),
+            Operator::Divide => match left.checked_div(right) {
+                Some(value) => value,
+                None => return None,
+            },
+            Operator::Modulo => match left.checked_rem(right) {
+                Some(value) => value,
+                None => return None,
+            },
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Relation {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    AllSet,
+    AnyClear,
+    Any,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Value {
+    Number(u64),
+    Float(f64),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Indirect {
+    pub(crate) number: Number,
+    pub(crate) signed: bool,
+    pub(crate) operator: Option<Operator>,
+    pub(crate) operand: i64,
+    pub(crate) operand_indirect: bool,
+    pub(crate) inverse: bool,
+    pub(crate) relative_result: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Offset {
+    pub(crate) value: i64,
+    pub(crate) relative: bool,
+    pub(crate) from_end: bool,
+    pub(crate) indirect: Option<Indirect>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum StrengthOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Rule {
+    pub(crate) level: u16,
+    pub(crate) offset: Offset,
+    pub(crate) kind: Kind,
+    pub(crate) mask: Option<(Operator, u64)>,
+    pub(crate) inverse: bool,
+    pub(crate) relation: Relation,
+    pub(crate) value: Value,
+    pub(crate) description: String,
+    pub(crate) no_space: bool,
+    pub(crate) mime: Option<String>,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) strength: Option<(StrengthOp, u8)>,
+}
+
+impl Rule {
+    /// Mirrors libmagic's `apprentice_magic_strength`, so rule sets are tried
+    /// in the same order GNU file tries them.
+    pub(crate) fn strength(&self) -> u32 {
+        const MULT: i64 = 10;
+        let mut value = 2 * MULT;
+        let length = match &self.value {
+            Value::Bytes(bytes) => bytes.len() as i64,
+            _ => 0,
+        };
+        match &self.kind {
+            Kind::Default => return 0,
+            Kind::Numeric { number, .. } => value += number.width as i64 * MULT,
+            Kind::String { .. } | Kind::PString { .. } => value += length * MULT,
+            Kind::String16 { .. } => value += length * MULT / 2,
+            Kind::Search { .. } => {
+                if length != 0 {
+                    value += length * (MULT / length).max(1);
+                }
+            }
+            Kind::Clear | Kind::Name(_) | Kind::Use { .. } | Kind::Unsupported => {}
+        }
+        match self.relation {
+            Relation::Any | Relation::NotEqual => value = 0,
+            Relation::Equal => value += MULT,
+            Relation::Less | Relation::Greater => value -= 2 * MULT,
+            Relation::AllSet | Relation::AnyClear => value -= MULT,
+        }
+        if let Some((op, factor)) = self.strength {
+            let factor = factor as i64;
+            value = match op {
+                StrengthOp::Add => value + factor,
+                StrengthOp::Subtract => value - factor,
+                StrengthOp::Multiply => value * factor,
+                StrengthOp::Divide => value.checked_div(factor).unwrap_or(value),
+            };
+        }
+        value.max(1) as u32
+    }
+
+    /// Whether a top-level rule belongs in the set tried against text buffers
+    /// rather than the set tried before any text checks.
+    pub(crate) fn is_text_test(&self) -> bool {
+        match &self.kind {
+            Kind::Search { flags, .. } => {
+                if flags & StringFlags::BINARY != 0 {
+                    return false;
+                }
+                if flags & StringFlags::TEXT != 0 {
+                    return true;
+                }
+                match &self.value {
+                    Value::Bytes(bytes) => std::str::from_utf8(bytes).is_ok_and(|text| {
+                        !text.is_empty()
+                            && text
+                                .chars()
+                                .all(|c| !c.is_control() || c.is_ascii_whitespace())
+                    }),
+                    _ => false,
+                }
+            }
+            Kind::String { flags } | Kind::PString { flags } => {
+                flags & StringFlags::TEXT != 0 && flags & StringFlags::BINARY == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the rule is a string test marked `/b`, which libmagic skips
+    /// for buffers that look like text.
+    pub(crate) const fn is_binary_test(&self) -> bool {
+        match self.kind {
+            Kind::String { flags } | Kind::PString { flags } | Kind::Search { flags, .. } => {
+                flags & StringFlags::BINARY != 0 && flags & StringFlags::TEXT == 0
+            }
+            _ => false,
+        }
+    }
+}
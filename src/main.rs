@@ -1,8 +1,231 @@
+use std::{
+    collections::VecDeque,
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Error as IOError, ErrorKind, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
 use file::*;
 
+const USAGE: &str =
+    "Usage: file [-0CLRhisz] [-f listfile] [--mime-type] [--mime-encoding] [--json | --ndjson] \
+                     [-j jobs] [--max-depth depth] [--exclude glob] [--gitignore] \
+                     [--zero-length auto|read|trust] [--uncompress-limit bytes] [--list-archive] \
+                     [-m magicfiles] [files]";
+
+/// Short flags that stand alone, which may be grouped as in USAGE, and
+/// those followed by a value, which may end a group.
+const SWITCHES: &str = "0CLRhisz";
+const VALUE_FLAGS: &str = "fjm";
+
+enum Mode {
+    Classify(Box<Options>, Vec<OsString>),
+    Compile(OsString),
+    /// Every `-f` list was empty.
+    Nothing,
+}
+
 fn main() {
-    let results = file(std::env::args_os().skip(1));
-    if results.is_err() {
-        eprintln!("{}. Usage: file [files]", results.unwrap_err());
+    let result = parse_args(env::args_os().skip(1)).and_then(|mode| match mode {
+        Mode::Classify(options, paths) => file_with_options(paths.into_iter(), &options),
+        Mode::Compile(list) => compile(list),
+        Mode::Nothing => Ok(()),
+    });
+    if let Err(error) = result {
+        eprintln!("{error}. {USAGE}");
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = OsString>) -> Result<Mode, IOError> {
+    let mut args = Args {
+        args,
+        pending: VecDeque::new(),
+    };
+    let mut options = Options::default();
+    let mut magic_list = None;
+    let mut compile = false;
+    let mut lists = Vec::new();
+    let mut nul_separated = false;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-m" | "--magic-file") => magic_list = Some(value(&arg, &mut args)?),
+            Some("-j" | "--jobs") => options.jobs = Some(number(&arg, &mut args)?),
+            Some("-R" | "--recursive") => options.recursive = true,
+            Some("--max-depth") => options.max_depth = Some(number(&arg, &mut args)?),
+            Some("--exclude") => options
+                .exclude
+                .push(value(&arg, &mut args)?.to_string_lossy().into_owned()),
+            Some("--gitignore") => options.gitignore = true,
+            Some("-L" | "--dereference") => options.follow_symlinks = true,
+            Some("-h" | "--no-dereference") => options.follow_symlinks = false,
+            Some("-s" | "--special-files") => options.read_devices = true,
+            Some("--zero-length") => {
+                options.zero_length = match value(&arg, &mut args)?.to_str() {
+                    Some("auto") => ZeroLength::PseudoFiles,
+                    Some("read") => ZeroLength::Read,
+                    Some("trust") => ZeroLength::Trust,
+                    _ => {
+                        return Err(IOError::new(
+                            ErrorKind::InvalidInput,
+                            "--zero-length must be auto, read or trust",
+                        ))
+                    }
+                }
+            }
+            Some("-z" | "--uncompress") => options.uncompress = true,
+            Some("--uncompress-limit") => options.uncompress_limit = Some(number(&arg, &mut args)?),
+            Some("--list-archive") => options.list_archive = true,
+            Some("-f" | "--files-from") => lists.push(value(&arg, &mut args)?),
+            Some("-0" | "--null") => nul_separated = true,
+            Some("-C" | "--compile") => compile = true,
+            Some("-i" | "--mime") => options.format = OutputFormat::Mime,
+            Some("--mime-type") => options.format = OutputFormat::MimeType,
+            Some("--mime-encoding") => options.format = OutputFormat::MimeEncoding,
+            Some("--json") => options.format = OutputFormat::Json,
+            Some("--ndjson") => options.format = OutputFormat::Ndjson,
+            Some("--") => paths.extend(args.by_ref()),
+            Some(group) if is_flag_group(group) => args.pending.extend(
+                group[1..]
+                    .chars()
+                    .map(|flag| OsString::from(format!("-{flag}"))),
+            ),
+            _ => paths.push(arg),
+        }
+    }
+    let listed = !lists.is_empty();
+    for list in lists {
+        paths.extend(read_list(&list, nul_separated)?);
+    }
+    if listed && paths.is_empty() && !compile {
+        return Ok(Mode::Nothing);
+    }
+    if compile {
+        let list = magic_list.or_else(|| env::var_os("MAGIC")).ok_or_else(|| {
+            IOError::new(
+                ErrorKind::InvalidInput,
+                "-C requires magic sources to compile",
+            )
+        })?;
+        return Ok(Mode::Compile(list));
+    }
+    options.magic = match magic_list {
+        Some(list) => Magic::load_list(list)?,
+        None => Magic::system()?,
+    };
+    warn(&options.magic);
+    Ok(Mode::Classify(Box::new(options), paths))
+}
+
+/// The command line, with grouped short flags such as `-iz` split into
+/// `-i` and `-z` as they are met.
+struct Args<I> {
+    args: I,
+    pending: VecDeque<OsString>,
+}
+
+impl<I: Iterator<Item = OsString>> Iterator for Args<I> {
+    type Item = OsString;
+
+    fn next(&mut self) -> Option<OsString> {
+        self.pending.pop_front().or_else(|| self.args.next())
+    }
+}
+
+/// Whether `arg` is several short flags after one dash, like getopt takes
+/// them. Only the last may be one that takes a value, which is then the
+/// next argument.
+fn is_flag_group(arg: &str) -> bool {
+    let Some(flags) = arg.strip_prefix('-') else {
+        return false;
+    };
+    let Some(last) = flags.chars().last() else {
+        return false;
+    };
+    flags.len() > 1
+        && flags[..flags.len() - 1]
+            .chars()
+            .all(|flag| SWITCHES.contains(flag))
+        && (SWITCHES.contains(last) || VALUE_FLAGS.contains(last))
+}
+
+/// Reads the paths in a `-f` list, one per line or NUL separated. A list
+/// named `-` is read from standard input.
+fn read_list(list: &OsStr, nul_separated: bool) -> Result<Vec<OsString>, IOError> {
+    let contents = if list == "-" {
+        let mut contents = Vec::new();
+        io::stdin().lock().read_to_end(&mut contents)?;
+        contents
+    } else {
+        fs::read(list).map_err(|error| {
+            IOError::new(
+                error.kind(),
+                format!("{}: {error}", Path::new(list).display()),
+            )
+        })?
+    };
+    let separator = if nul_separated { b'\0' } else { b'\n' };
+    Ok(contents
+        .split(|&byte| byte == separator)
+        .filter(|path| !path.is_empty())
+        .map(os_string)
+        .collect())
+}
+
+#[cfg(unix)]
+fn os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn value(arg: &OsString, args: &mut impl Iterator<Item = OsString>) -> Result<OsString, IOError> {
+    args.next()
+        .ok_or_else(|| IOError::new(ErrorKind::InvalidInput, format!("{arg:?} requires a value")))
+}
+
+fn number<T: FromStr>(
+    arg: &OsString,
+    args: &mut impl Iterator<Item = OsString>,
+) -> Result<T, IOError> {
+    value(arg, args)?
+        .to_str()
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| {
+            IOError::new(
+                ErrorKind::InvalidInput,
+                format!("{arg:?} requires a positive number"),
+            )
+        })
+}
+
+/// Reports the magic source lines that were skipped, which GNU file also
+/// only warns about.
+fn warn(magic: &Magic) {
+    for warning in magic.warnings() {
+        eprintln!("warning: {warning}");
+    }
+}
+
+/// Compiles each source file or directory in `list` into `<name>.magicdb`
+/// in the current directory, like `file -C` does with `.mgc`. The database
+/// is this crate's own format, which libmagic cannot read.
+fn compile(list: OsString) -> Result<(), IOError> {
+    for path in env::split_paths(&list).filter(|path| !path.as_os_str().is_empty()) {
+        let magic = Magic::load(&path)
+            .map_err(|error| IOError::new(error.kind(), format!("{}: {error}", path.display())))?;
+        warn(&magic);
+        let mut output = path.file_name().map_or_else(OsString::new, OsString::from);
+        output.push(".");
+        output.push(Magic::DATABASE_EXTENSION);
+        fs::write(PathBuf::from(output), magic.compile())?;
     }
+    Ok(())
 }
@@ -0,0 +1,222 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufReader, Cursor, Error as IOError, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    classify_file_with_options,
+    formats::{archive, compressed},
+    special, BufferState, BufferType, Classification, Options, UNCOMPRESS_LIMIT,
+};
+
+/// How much of an archive is read to tell zip, tar and compressed tar apart.
+const HEAD_SIZE: u64 = 4096;
+
+/// Classifies every member of a zip or tar archive, including a tar inside
+/// a gzip, bzip2, xz or Zstandard stream, for `--list-archive`. Members are
+/// named `archive!path/inside`. A failure part way through is reported
+/// under `archive!` after the members read so far.
+pub(crate) fn members(
+    path: &Path,
+    classification: &Classification,
+    options: &Options,
+) -> Vec<(PathBuf, BufferState)> {
+    let mut members = Vec::new();
+    if matches!(
+        classification.buffer_type,
+        BufferType::Archive | BufferType::Compressed
+    ) {
+        if let Err(error) = list(path, options, &mut members) {
+            members.push((member(path, b""), Err(error)));
+        }
+    }
+    members
+}
+
+fn list(
+    path: &Path,
+    options: &Options,
+    members: &mut Vec<(PathBuf, BufferState)>,
+) -> Result<(), IOError> {
+    let mut file = File::open(path)?;
+    let mut head = Vec::new();
+    file.by_ref().take(HEAD_SIZE).read_to_end(&mut head)?;
+    if archive::is_zip(&head) {
+        file.rewind()?;
+        return zip(path, BufReader::new(file), options, members);
+    }
+    let reader = Cursor::new(head.clone()).chain(file);
+    if archive::is_tar(&head) {
+        return tar(path, reader, options, members);
+    }
+    let Some(decoder) = compressed::decoder(&head, reader) else {
+        return Ok(());
+    };
+    // the whole stream is read, so bound it like -z does
+    let limit = options.uncompress_limit.unwrap_or(UNCOMPRESS_LIMIT);
+    let mut decoder = decoder.take(limit);
+    let mut inner = Vec::new();
+    decoder
+        .by_ref()
+        .take(archive::TAR_BLOCK as u64)
+        .read_to_end(&mut inner)?;
+    if !archive::is_tar(&inner) {
+        return Ok(());
+    }
+    tar(path, Cursor::new(inner).chain(decoder), options, members)
+}
+
+fn tar(
+    path: &Path,
+    reader: impl Read,
+    options: &Options,
+    members: &mut Vec<(PathBuf, BufferState)>,
+) -> Result<(), IOError> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = member(path, &entry.path_bytes());
+        let kind = entry.header().entry_type();
+        let data = if kind.is_dir() {
+            Ok(Classification::new(BufferType::Directory))
+        } else if kind.is_symlink() {
+            let target = entry.link_name_bytes().unwrap_or_default();
+            Ok(special::link_to(&os_path(&target), false))
+        } else if kind.is_file() {
+            classify_file_with_options(&mut entry, options)
+        } else {
+            // hard links name a member listed already; devices and fifos
+            // carry no contents
+            continue;
+        };
+        members.push((name, data));
+    }
+    Ok(())
+}
+
+fn zip(
+    path: &Path,
+    reader: impl Read + Seek,
+    options: &Options,
+    members: &mut Vec<(PathBuf, BufferState)>,
+) -> Result<(), IOError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let limit = options.uncompress_limit.unwrap_or(UNCOMPRESS_LIMIT);
+    for index in 0..archive.len() {
+        let name = member(
+            path,
+            archive.name_for_index(index).unwrap_or_default().as_bytes(),
+        );
+        let data = match archive.by_index(index) {
+            Ok(entry) if entry.is_dir() => Ok(Classification::new(BufferType::Directory)),
+            Ok(entry) if entry.is_symlink() => {
+                let mut target = Vec::new();
+                entry
+                    .take(limit)
+                    .read_to_end(&mut target)
+                    .map(|_| special::link_to(&os_path(&target), false))
+            }
+            Ok(entry) => classify_file_with_options(entry.take(limit), options),
+            Err(error) => Err(error.into()),
+        };
+        members.push((name, data));
+    }
+    Ok(())
+}
+
+/// Names a member as `archive!name`.
+fn member(archive: &Path, name: &[u8]) -> PathBuf {
+    let mut path = OsString::from(archive);
+    path.push("!");
+    path.push(os_path(name));
+    PathBuf::from(path)
+}
+
+#[cfg(unix)]
+fn os_path(bytes: &[u8]) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use super::*;
+
+    fn describe(members: Vec<(PathBuf, BufferState)>, root: &Path) -> Vec<(String, String)> {
+        members
+            .into_iter()
+            .map(|(path, data)| {
+                let path = path.to_string_lossy();
+                let name = path.strip_prefix(&*root.to_string_lossy()).unwrap();
+                (name.to_owned(), data.unwrap().description)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_members() {
+        let root = std::env::temp_dir().join(format!("file-rs-members-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let options = Options::default();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "src/", std::io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(6);
+        builder
+            .append_data(&mut header, "src/lib.rs", b"hello\n".as_slice())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "lib", "src/lib.rs")
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&tar).unwrap();
+        let tar_path = root.join("source.tar.gz");
+        fs::write(&tar_path, encoder.finish().unwrap()).unwrap();
+        let classification = crate::classify_file(fs::read(&tar_path).unwrap().as_slice()).unwrap();
+        let found = describe(members(&tar_path, &classification, &options), &tar_path);
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("data.bin", stored).unwrap();
+        writer.write_all(&[0; 16]).unwrap();
+        let zip_path = root.join("bundle.zip");
+        fs::write(&zip_path, writer.finish().unwrap().into_inner()).unwrap();
+        let classification = crate::classify_file(fs::read(&zip_path).unwrap().as_slice()).unwrap();
+        let zipped = describe(members(&zip_path, &classification, &options), &zip_path);
+
+        let text = crate::classify_file(b"text".as_slice()).unwrap();
+        assert!(members(&zip_path, &text, &options).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+
+        let expected = [
+            ("!src/", "directory"),
+            ("!src/lib.rs", "ASCII text"),
+            ("!lib", "symbolic link to src/lib.rs"),
+        ];
+        assert_eq!(
+            found,
+            expected.map(|(name, description)| (name.to_owned(), description.to_owned()))
+        );
+        assert_eq!(zipped, [("!data.bin".to_owned(), "data".to_owned())]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, Metadata},
+    io::Error as IOError,
+    path::Path,
+};
+
+use crate::{BufferType, Classification};
+
+/// Classifies anything that is not a regular file from its metadata alone,
+/// the way GNU file does. Returns `None` for regular files, and for devices
+/// when `read_devices` asks for their contents to be read instead.
+pub(crate) fn classify(
+    path: &Path,
+    metadata: &Metadata,
+    read_devices: bool,
+) -> Result<Option<Classification>, IOError> {
+    let file_type = metadata.file_type();
+    if file_type.is_file() {
+        return Ok(None);
+    }
+    if file_type.is_dir() {
+        return Ok(Some(Classification::new(BufferType::Directory)));
+    }
+    if file_type.is_symlink() {
+        return symlink(path).map(Some);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+        if file_type.is_fifo() {
+            return Ok(Some(Classification::new(BufferType::Fifo)));
+        }
+        if file_type.is_socket() {
+            return Ok(Some(Classification::new(BufferType::Socket)));
+        }
+        let buffer_type = if file_type.is_block_device() {
+            BufferType::BlockDevice
+        } else {
+            BufferType::CharDevice
+        };
+        if read_devices {
+            return Ok(None);
+        }
+        let (major, minor) = device_numbers(metadata.rdev());
+        let mut classification = Classification::new(buffer_type);
+        classification.description = format!("{} ({major}/{minor})", buffer_type.description());
+        Ok(Some(classification))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = read_devices;
+        Ok(None)
+    }
+}
+
+/// Describes a link without following it, noting when its target is missing.
+pub(crate) fn symlink(path: &Path) -> Result<Classification, IOError> {
+    let target = fs::read_link(path)?;
+    let broken = fs::metadata(path).is_err();
+    Ok(link_to(&target, broken))
+}
+
+/// Describes a link to `target`, on disk or inside an archive.
+pub(crate) fn link_to(target: &Path, broken: bool) -> Classification {
+    let mut classification = Classification::new(BufferType::Symlink);
+    classification.description = format!(
+        "{}symbolic link to {}",
+        if broken { "broken " } else { "" },
+        target.display()
+    );
+    classification
+}
+
+/// Whether a file lives on a kernel pseudo filesystem such as procfs or
+/// sysfs, where files report a length of zero but still have contents.
+pub(crate) fn is_pseudo_file(path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+        let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `path` is a valid C string and `stat` is only read once
+        // statfs has filled it in
+        if unsafe { libc::statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+            return false;
+        }
+        let kind = unsafe { stat.assume_init() }.f_type as u32;
+        PSEUDO_FILESYSTEMS.contains(&kind)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// The `statfs` types of filesystems whose files are generated when read,
+/// from `linux/magic.h`.
+#[cfg(target_os = "linux")]
+const PSEUDO_FILESYSTEMS: [u32; 11] = [
+    0x0000_9FA0, // proc
+    0x6265_6572, // sysfs
+    0x0027_E0EB, // cgroup
+    0x6367_7270, // cgroup2
+    0x6462_6720, // debugfs
+    0x7472_6163, // tracefs
+    0x7363_6673, // securityfs
+    0x6265_6570, // configfs
+    0x6165_676C, // pstore
+    0xCAFE_4A11, // bpf
+    0x4249_4E4D, // binfmt_misc
+];
+
+/// Splits a device number the way glibc's `major` and `minor` macros do.
+#[cfg(unix)]
+const fn device_numbers(rdev: u64) -> (u64, u64) {
+    let major = ((rdev >> 8) & 0xFFF) | ((rdev >> 32) & !0xFFF);
+    let minor = (rdev & 0xFF) | ((rdev >> 12) & !0xFF);
+    (major, minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let directory = fs::metadata("test_files").unwrap();
+        let classification = classify(Path::new("test_files"), &directory, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(classification.description, "directory");
+        assert_eq!(classification.mime(), "inode/directory; charset=binary");
+        let file = fs::metadata("test_files/ascii.txt").unwrap();
+        assert!(classify(Path::new("test_files/ascii.txt"), &file, false)
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special() {
+        let null = fs::metadata("/dev/null").unwrap();
+        let classification = classify(Path::new("/dev/null"), &null, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(classification.description, "character special (1/3)");
+        assert!(classify(Path::new("/dev/null"), &null, true)
+            .unwrap()
+            .is_none());
+        let link = std::env::temp_dir().join(format!("file-rs-link-{}", std::process::id()));
+        std::os::unix::fs::symlink("missing target", &link).unwrap();
+        let broken = symlink(&link);
+        fs::remove_file(&link).unwrap();
+        assert_eq!(
+            broken.unwrap().description,
+            "broken symbolic link to missing target"
+        );
+        assert_eq!(device_numbers(0x0801), (8, 1));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_pseudo_file() {
+        let cmdline = Path::new("/proc/self/cmdline");
+        assert_eq!(fs::metadata(cmdline).unwrap().len(), 0);
+        assert!(is_pseudo_file(cmdline));
+        assert!(is_pseudo_file(Path::new("/sys/kernel")));
+        assert!(!is_pseudo_file(Path::new("test_files/empty")));
+    }
+}
@@ -1,11 +1,258 @@
 pub(crate) mod gb_sequence;
 pub(crate) mod unicode;
 
+use std::sync::OnceLock;
+
+use crate::{
+    is_byte_latin1,
+    vle::{
+        gb_sequence::GbSequence,
+        unicode::{
+            utf16sequence::Utf16Validator, utf32sequence::Utf32Validator,
+            utf8sequence::Utf8Sequence,
+        },
+    },
+    BufferType, Endianness,
+};
+
+/// One character of a variable length text encoding, built up a point at a
+/// time. The text checks start a sequence at every point that does not
+/// continue the previous one, and the text is only valid in the encoding
+/// while every sequence is.
 pub trait VariableLengthEncoding: Sized {
+    /// The unit the encoding is made of, such as a byte.
     type Point;
 
+    /// Starts a sequence with its first point, or returns `None` when no
+    /// sequence can start with it.
     fn build(input: Self::Point) -> Option<Self>;
+    /// Whether the sequence has all of its points.
     fn is_complete(&self) -> bool;
+    /// Adds the next point, returning whether the sequence can still be
+    /// valid.
     fn add_point(&mut self, point: Self::Point) -> bool;
+    /// Whether the complete sequence is a character that occurs in text.
     fn is_valid(&self) -> bool;
 }
+
+/// Checks whether a stream of bytes stays valid in one encoding.
+pub(crate) trait Validator {
+    /// Takes the next bytes, returning whether the text is still valid.
+    fn push(&mut self, bytes: &[u8]) -> bool;
+    /// Whether the text is valid now that it has ended.
+    fn finish(&self) -> bool;
+    /// The byte order found, for encodings that have one.
+    fn endianness(&self) -> Option<Endianness> {
+        None
+    }
+    /// Whether the text started with a byte order mark that this encoding
+    /// read, rather than a character.
+    fn bom(&self) -> bool {
+        false
+    }
+}
+
+/// A text encoding tried by the text checks, along with where it ranks
+/// among the others.
+pub struct Encoding {
+    buffer_type: BufferType,
+    priority: i32,
+    /// Makes a validator for text whose first byte pushed is at the given
+    /// offset.
+    validator: fn(usize) -> Box<dyn Validator>,
+}
+
+impl Encoding {
+    /// An encoding whose characters `T` validates a byte at a time. It is
+    /// reported as [`BufferType::Text`] with `name` as the description, such
+    /// as `Shift_JIS text`, and `charset` in MIME output.
+    ///
+    /// Like the built-in encodings, it must be a superset of ASCII: text
+    /// is only passed to `T` from the start of the 8 KiB chunk holding the
+    /// first byte outside ASCII.
+    pub fn new<T: VariableLengthEncoding<Point = u8> + 'static>(
+        name: &'static str,
+        charset: &'static str,
+        priority: i32,
+    ) -> Self {
+        Self {
+            buffer_type: BufferType::Text { name, charset },
+            priority,
+            validator: |_| Box::new(Sequences::<T>::default()),
+        }
+    }
+
+    pub fn buffer_type(&self) -> BufferType {
+        self.buffer_type
+    }
+
+    /// Valid encodings with a higher priority are preferred; see
+    /// [`Encodings::new`].
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub(crate) fn validator(&self, start: usize) -> Box<dyn Validator> {
+        (self.validator)(start)
+    }
+
+    fn builtin<T: VariableLengthEncoding<Point = u8> + 'static>(
+        buffer_type: BufferType,
+        priority: i32,
+    ) -> Self {
+        Self {
+            buffer_type,
+            priority,
+            validator: |_| Box::new(Sequences::<T>::default()),
+        }
+    }
+}
+
+/// The text encodings tried on every file, in priority order. ASCII is not
+/// one of them: it is always checked first, and wins whenever it is valid.
+pub struct Encodings {
+    encodings: Vec<Encoding>,
+}
+
+impl Encodings {
+    /// The built-in encodings: UTF-32 with a byte order mark at priority 40,
+    /// UTF-16 with or without a byte order mark at 30, UTF-8 at 20, ISO 8859-1 at 10
+    /// and GB 18030 at 0. UTF-32 goes first because its little-endian mark
+    /// starts with the UTF-16 one.
+    pub fn new() -> Self {
+        let mut encodings = Self::empty();
+        encodings.register(Encoding {
+            buffer_type: BufferType::Utf32,
+            priority: 40,
+            validator: |start| Box::new(Utf32Validator::new(start)),
+        });
+        encodings.register(Encoding {
+            buffer_type: BufferType::Utf16,
+            priority: 30,
+            validator: |start| Box::new(Utf16Validator::new(start)),
+        });
+        encodings.register(Encoding::builtin::<Utf8Sequence>(BufferType::Utf8, 20));
+        encodings.register(Encoding {
+            buffer_type: BufferType::Latin1,
+            priority: 10,
+            validator: |_| Box::new(Latin1 { is_valid: true }),
+        });
+        encodings.register(Encoding::builtin::<GbSequence>(BufferType::Gb, 0));
+        encodings
+    }
+
+    /// A registry without any encodings, so only ASCII is text.
+    pub fn empty() -> Self {
+        Self {
+            encodings: Vec::new(),
+        }
+    }
+
+    /// Adds `encoding` after every registered encoding of the same or a
+    /// higher priority.
+    pub fn register(&mut self, encoding: Encoding) {
+        let at = self
+            .encodings
+            .partition_point(|registered| registered.priority >= encoding.priority);
+        self.encodings.insert(at, encoding);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.encodings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.encodings.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Encoding> {
+        self.encodings.iter()
+    }
+
+    /// The built-in encodings, shared by the entry points that take no
+    /// [`Options`](crate::Options).
+    pub(crate) fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<Encodings> = OnceLock::new();
+        BUILTIN.get_or_init(Self::new)
+    }
+}
+
+impl Default for Encodings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Validates text as a run of `T` sequences.
+pub(crate) struct Sequences<T> {
+    pub(crate) is_valid: bool,
+    sequence: Option<T>,
+}
+
+impl<T> Default for Sequences<T> {
+    fn default() -> Self {
+        Self {
+            is_valid: true,
+            sequence: None,
+        }
+    }
+}
+
+impl<T: VariableLengthEncoding> Sequences<T> {
+    #[inline]
+    pub(crate) fn push_point(&mut self, point: T::Point) -> bool {
+        if let Some(sequence) = self.sequence.as_mut() {
+            if !sequence.is_complete() && !sequence.add_point(point) {
+                self.is_valid = false;
+                return false;
+            }
+            if sequence.is_complete() {
+                if !sequence.is_valid() {
+                    self.is_valid = false;
+                }
+                self.sequence = None;
+            }
+        } else if let Some(sequence) = T::build(point) {
+            if !sequence.is_complete() {
+                self.sequence = Some(sequence);
+            } else if !sequence.is_valid() {
+                self.is_valid = false;
+            }
+        } else {
+            self.is_valid = false;
+        }
+        self.is_valid
+    }
+
+    /// Whether every sequence was valid and the last one was finished.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.is_valid && self.sequence.is_none()
+    }
+}
+
+impl<T: VariableLengthEncoding<Point = u8>> Validator for Sequences<T> {
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        bytes.iter().all(|&byte| self.push_point(byte))
+    }
+
+    fn finish(&self) -> bool {
+        self.is_finished()
+    }
+}
+
+/// ISO 8859-1, where every byte is a character and only the control ranges
+/// are out of place in text.
+struct Latin1 {
+    is_valid: bool,
+}
+
+impl Validator for Latin1 {
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        self.is_valid &= bytes.iter().all(|&byte| is_byte_latin1(byte));
+        self.is_valid
+    }
+
+    fn finish(&self) -> bool {
+        self.is_valid
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod utf16sequence;
+pub(crate) mod utf32sequence;
 pub(crate) mod utf8sequence;
 pub(crate) const fn is_text(codepoint: u32) -> bool {
     if char::from_u32(codepoint).is_none() {
@@ -11,8 +12,9 @@ pub(crate) const fn is_text(codepoint: u32) -> bool {
         && 0xA0 > codepoint)
 }
 
-#[derive(Copy, Clone)]
-pub(crate) enum Endianness {
+/// The byte order of UTF-16 or UTF-32 text, as given by its byte order mark.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
     BigEndian,
     LittleEndian,
 }
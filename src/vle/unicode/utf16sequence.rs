@@ -1,4 +1,4 @@
-use crate::vle::{unicode::*, VariableLengthEncoding};
+use crate::vle::{unicode::*, Sequences, Validator, VariableLengthEncoding};
 
 #[derive(Clone, Copy)]
 enum Utf16Type {
@@ -85,3 +85,143 @@ impl Utf16Sequence {
         }
     }
 }
+
+/// How many code units of text without a byte order mark are read before
+/// giving up on it when its zero bytes do not suggest UTF-16.
+const GUESS_SAMPLE: usize = 256;
+
+enum Mode {
+    /// Nothing has been read yet.
+    Start,
+    /// The text started with a byte order mark.
+    Bom(Endianness),
+    /// The text has no byte order mark, so both byte orders are validated
+    /// and the zero bytes decide between them.
+    Guess,
+}
+
+/// Validates UTF-16 text. Text with a byte order mark at its start is read
+/// in that order. Text without one is taken for UTF-16 when most of its
+/// code units have a zero high byte, as text mostly below U+0100 does, and
+/// is valid in that byte order.
+pub(crate) struct Utf16Validator {
+    mode: Mode,
+    /// The first byte of a code unit whose second has not been pushed yet.
+    pending: Option<u8>,
+    big_endian: Sequences<Utf16Sequence>,
+    little_endian: Sequences<Utf16Sequence>,
+    /// Zero bytes at even and at odd offsets, without a byte order mark.
+    zeros: [usize; 2],
+    units: usize,
+}
+
+impl Utf16Validator {
+    /// A validator for text whose first byte pushed is at offset `start`.
+    /// Only text pushed from its start can be told apart from its byte
+    /// order mark or the offsets of its zero bytes.
+    pub(crate) fn new(start: usize) -> Self {
+        let mut validator = Self {
+            mode: Mode::Start,
+            pending: None,
+            big_endian: Sequences::default(),
+            little_endian: Sequences::default(),
+            zeros: [0; 2],
+            units: 0,
+        };
+        if start != 0 {
+            validator.invalidate();
+        }
+        validator
+    }
+
+    fn invalidate(&mut self) {
+        self.big_endian.is_valid = false;
+        self.little_endian.is_valid = false;
+    }
+
+    fn is_valid(&self) -> bool {
+        match self.mode {
+            Mode::Bom(Endianness::BigEndian) => self.big_endian.is_valid,
+            Mode::Bom(Endianness::LittleEndian) => self.little_endian.is_valid,
+            _ => self.big_endian.is_valid || self.little_endian.is_valid,
+        }
+    }
+
+    /// The byte order the zero bytes point to, when most code units have a
+    /// zero high byte and few have a zero low byte.
+    fn guess(&self) -> Option<Endianness> {
+        let [even, odd] = self.zeros;
+        if odd * 2 >= self.units && even * 10 <= odd {
+            Some(Endianness::LittleEndian)
+        } else if even * 2 >= self.units && odd * 10 <= even {
+            Some(Endianness::BigEndian)
+        } else {
+            None
+        }
+    }
+
+    fn push_unit(&mut self, pair: [u8; 2]) {
+        if let Mode::Start = self.mode {
+            self.mode = match pair {
+                [0xFE, 0xFF] => Mode::Bom(Endianness::BigEndian),
+                [0xFF, 0xFE] => Mode::Bom(Endianness::LittleEndian),
+                _ => Mode::Guess,
+            };
+            if let Mode::Bom(_) = self.mode {
+                return;
+            }
+        }
+        let check_big = !matches!(self.mode, Mode::Bom(Endianness::LittleEndian));
+        let check_little = !matches!(self.mode, Mode::Bom(Endianness::BigEndian));
+        if check_big && self.big_endian.is_valid {
+            self.big_endian.push_point(u16::from_be_bytes(pair));
+        }
+        if check_little && self.little_endian.is_valid {
+            self.little_endian.push_point(u16::from_le_bytes(pair));
+        }
+        if let Mode::Guess = self.mode {
+            self.zeros[0] += usize::from(pair[0] == 0);
+            self.zeros[1] += usize::from(pair[1] == 0);
+            self.units += 1;
+            if self.units == GUESS_SAMPLE && self.guess().is_none() {
+                self.invalidate();
+            }
+        }
+    }
+}
+
+impl Validator for Utf16Validator {
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        for &byte in bytes {
+            if !self.is_valid() {
+                return false;
+            }
+            match self.pending.take() {
+                Some(first) => self.push_unit([first, byte]),
+                None => self.pending = Some(byte),
+            }
+        }
+        self.is_valid()
+    }
+
+    fn finish(&self) -> bool {
+        match self.endianness() {
+            Some(Endianness::BigEndian) => self.big_endian.is_finished(),
+            Some(Endianness::LittleEndian) => self.little_endian.is_finished(),
+            None => false,
+        }
+    }
+
+    fn endianness(&self) -> Option<Endianness> {
+        match self.mode {
+            Mode::Bom(endianness) => Some(endianness),
+            // a single code unit says nothing about the byte order
+            Mode::Guess if self.units >= 2 => self.guess(),
+            _ => None,
+        }
+    }
+
+    fn bom(&self) -> bool {
+        matches!(self.mode, Mode::Bom(_))
+    }
+}
@@ -0,0 +1,96 @@
+use crate::vle::{unicode::*, Sequences, Validator, VariableLengthEncoding};
+
+/// A UTF-32 code unit, which is always a whole character.
+pub(crate) struct Utf32Sequence(u32);
+
+impl VariableLengthEncoding for Utf32Sequence {
+    type Point = u32;
+
+    #[inline]
+    fn build(point: Self::Point) -> Option<Self> {
+        Some(Self(point))
+    }
+
+    #[inline]
+    fn is_complete(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn add_point(&mut self, _: Self::Point) -> bool {
+        false
+    }
+
+    #[inline]
+    fn is_valid(&self) -> bool {
+        // surrogates and values past U+10FFFF are not characters
+        matches!(self.0, 0x0000..=0xD7FF | 0xE000..=0x10_FFFF) && is_text(self.0)
+    }
+}
+
+/// Validates UTF-32 text, which is only recognised by the byte order mark
+/// at its start.
+pub(crate) struct Utf32Validator {
+    endianness: Option<Endianness>,
+    /// The bytes of a code unit whose last byte has not been pushed yet.
+    pending: Vec<u8>,
+    sequences: Sequences<Utf32Sequence>,
+}
+
+impl Utf32Validator {
+    /// A validator for text whose first byte pushed is at offset `start`. A
+    /// byte order mark can only be there when that is the start of the
+    /// text.
+    pub(crate) fn new(start: usize) -> Self {
+        let mut sequences = Sequences::default();
+        if start != 0 {
+            sequences.is_valid = false;
+        }
+        Self {
+            endianness: None,
+            pending: Vec::with_capacity(4),
+            sequences,
+        }
+    }
+}
+
+impl Validator for Utf32Validator {
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        for &byte in bytes {
+            if !self.sequences.is_valid {
+                return false;
+            }
+            self.pending.push(byte);
+            let Ok(unit) = <[u8; 4]>::try_from(self.pending.as_slice()) else {
+                continue;
+            };
+            self.pending.clear();
+            match self.endianness {
+                Some(Endianness::BigEndian) => {
+                    self.sequences.push_point(u32::from_be_bytes(unit));
+                }
+                Some(Endianness::LittleEndian) => {
+                    self.sequences.push_point(u32::from_le_bytes(unit));
+                }
+                None => match unit {
+                    [0x00, 0x00, 0xFE, 0xFF] => self.endianness = Some(Endianness::BigEndian),
+                    [0xFF, 0xFE, 0x00, 0x00] => self.endianness = Some(Endianness::LittleEndian),
+                    _ => self.sequences.is_valid = false,
+                },
+            }
+        }
+        self.sequences.is_valid
+    }
+
+    fn finish(&self) -> bool {
+        self.endianness.is_some() && self.sequences.is_finished()
+    }
+
+    fn endianness(&self) -> Option<Endianness> {
+        self.endianness
+    }
+
+    fn bom(&self) -> bool {
+        self.endianness.is_some()
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    io::{Error as IOError, ErrorKind},
+    path::{Path, PathBuf},
+    sync::mpsc::SyncSender,
+};
+
+use ignore::{
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder, WalkState,
+};
+
+use crate::Options;
+
+/// Work for the pool in [`file_with_options`](crate::file_with_options): a
+/// file to classify, or a failure found while walking to one.
+pub(crate) enum Job {
+    Classify(PathBuf),
+    /// The `-` argument.
+    Stdin,
+    Failed(PathBuf, IOError),
+}
+
+/// Sends every path to the worker pool, walking directories in parallel when
+/// `options.recursive` is set. Stops early once the pool has gone away.
+pub(crate) fn send_paths(paths: &[PathBuf], options: &Options, jobs: &SyncSender<Job>) {
+    for path in paths {
+        let job = if path.as_os_str() == "-" {
+            Job::Stdin
+        } else if options.recursive && path.is_dir() {
+            walk(path, options, jobs);
+            continue;
+        } else {
+            Job::Classify(path.clone())
+        };
+        if jobs.send(job).is_err() {
+            return;
+        }
+    }
+}
+
+/// Builds the `--exclude` globs for a walk rooted at `root`. Globs follow
+/// `.gitignore` syntax, so one without a slash matches at any depth.
+pub(crate) fn exclusions(root: &Path, globs: &[String]) -> Result<Override, IOError> {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in globs {
+        builder.add(&format!("!{glob}")).map_err(|error| {
+            let reason = match error {
+                ignore::Error::Glob { err, .. } => err,
+                error => error.to_string(),
+            };
+            IOError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid exclude glob {glob:?}: {reason}"),
+            )
+        })?;
+    }
+    builder
+        .build()
+        .map_err(|error| IOError::new(ErrorKind::InvalidInput, error.to_string()))
+}
+
+fn walk(root: &Path, options: &Options, jobs: &SyncSender<Job>) {
+    let overrides = match exclusions(root, &options.exclude) {
+        Ok(overrides) => overrides,
+        Err(error) => {
+            let _ = jobs.send(Job::Failed(root.to_owned(), error));
+            return;
+        }
+    };
+    WalkBuilder::new(root)
+        .standard_filters(false)
+        .git_ignore(options.gitignore)
+        .git_exclude(options.gitignore)
+        .parents(options.gitignore)
+        .require_git(false)
+        // loops through followed links are reported as errors
+        .follow_links(options.follow_symlinks)
+        .max_depth(options.max_depth)
+        .overrides(overrides)
+        .threads(options.worker_count())
+        .build_parallel()
+        .run(|| {
+            let jobs = jobs.clone();
+            Box::new(move |entry| {
+                let job = match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_dir()) => {
+                        return WalkState::Continue;
+                    }
+                    Ok(entry) => Job::Classify(entry.into_path()),
+                    Err(error) => match walk_error(root, error) {
+                        // described like a broken link given on the command line
+                        (path, error)
+                            if error.kind() == ErrorKind::NotFound && path.is_symlink() =>
+                        {
+                            Job::Classify(path)
+                        }
+                        (path, error) => Job::Failed(path, error),
+                    },
+                };
+                match jobs.send(job) {
+                    Ok(()) => WalkState::Continue,
+                    Err(_) => WalkState::Quit,
+                }
+            })
+        });
+}
+
+/// Splits a walk error into the path it concerns and a plain I/O error.
+fn walk_error(root: &Path, error: ignore::Error) -> (PathBuf, IOError) {
+    match error {
+        ignore::Error::WithPath { path, err } => (path, walk_error(root, *err).1),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error(root, *err)
+        }
+        ignore::Error::Loop { child, .. } => (child, IOError::other("symbolic link loop")),
+        ignore::Error::Io(error) => (root.to_owned(), error),
+        error => (root.to_owned(), IOError::other(error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::mpsc};
+
+    use super::*;
+
+    #[test]
+    fn test_send_paths() {
+        let root = std::env::temp_dir().join(format!("file-rs-walk-{}", std::process::id()));
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for file in ["top.txt", "a/skip.log", "a/b/deep.txt", "a/b/ignored.txt"] {
+            fs::write(root.join(file), "text").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "ignored.txt\n").unwrap();
+        let options = Options {
+            recursive: true,
+            max_depth: Some(3),
+            exclude: vec![String::from("*.log")],
+            gitignore: true,
+            ..Options::default()
+        };
+        let (sender, receiver) = mpsc::sync_channel(16);
+        send_paths(std::slice::from_ref(&root), &options, &sender);
+        drop(sender);
+        let mut found: Vec<_> = receiver
+            .into_iter()
+            .map(|job| match job {
+                Job::Classify(path) => path.strip_prefix(&root).unwrap().to_owned(),
+                Job::Stdin => panic!("unexpected stdin"),
+                Job::Failed(path, error) => panic!("{}: {error}", path.display()),
+            })
+            .collect();
+        found.sort();
+        fs::remove_dir_all(&root).unwrap();
+        let expected: [PathBuf; 3] = [".gitignore".into(), "a/b/deep.txt".into(), "top.txt".into()];
+        assert_eq!(found, expected);
+        assert!(exclusions(Path::new(""), &[String::from("[")]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_link() {
+        let root = std::env::temp_dir().join(format!("file-rs-broken-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("link")).unwrap();
+        let options = Options {
+            recursive: true,
+            follow_symlinks: true,
+            ..Options::default()
+        };
+        let (sender, receiver) = mpsc::sync_channel(16);
+        send_paths(std::slice::from_ref(&root), &options, &sender);
+        drop(sender);
+        let jobs: Vec<_> = receiver.into_iter().collect();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(matches!(&jobs[..], [Job::Classify(path)] if path == &root.join("link")));
+    }
+}